@@ -1,7 +1,13 @@
 //! Benchmarking the sliding window of the Rabin64 algorithm
 
+// `criterion_group!` generates an undocumented public function.
+#![allow(missing_docs)]
+
 use criterion::{criterion_group, criterion_main, Criterion};
-use rustic_cdc::{Rabin64, RollingHash64};
+use rustic_cdc::{
+    AeSeparatorIter, KnownChunks, QuickSeparatorIter, Rabin64, RamSeparatorIter, RollingHash64,
+    SeparatorIter, TttdSeparatorIter,
+};
 
 /// Benchmark the sliding window of the Rabin64 algorithm
 ///
@@ -19,5 +25,37 @@ pub fn slide_benchmarks(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, slide_benchmarks);
+/// Benchmark the separator algorithms, with an expected chunk size of 8 KiB
+///
+pub fn separator_benchmarks(c: &mut Criterion) {
+    let data: Vec<u8> = (0..1 << 20)
+        .scan(0x2545_f491_4f6c_dd1du64, |state, _| {
+            *state ^= *state << 13;
+            *state ^= *state >> 7;
+            *state ^= *state << 17;
+            Some(state.to_le_bytes()[0])
+        })
+        .collect();
+    let mut group = c.benchmark_group("separators 1 MiB");
+    _ = group.bench_function("rabin", |b| {
+        b.iter(|| SeparatorIter::new(data.iter().copied()).count());
+    });
+    _ = group.bench_function("ae", |b| {
+        b.iter(|| AeSeparatorIter::new(data.iter().copied()).count());
+    });
+    _ = group.bench_function("ram", |b| {
+        b.iter(|| RamSeparatorIter::new(data.iter().copied()).count());
+    });
+    _ = group.bench_function("tttd", |b| {
+        b.iter(|| TttdSeparatorIter::new(data.iter().copied()).count());
+    });
+    _ = group.bench_function("quick_known", |b| {
+        let mut known = KnownChunks::new();
+        _ = QuickSeparatorIter::new(data.iter().copied(), &mut known).count();
+        b.iter(|| QuickSeparatorIter::new(data.iter().copied(), &mut known).count());
+    });
+    group.finish();
+}
+
+criterion_group!(benches, slide_benchmarks, separator_benchmarks);
 criterion_main!(benches);
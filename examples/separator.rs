@@ -27,6 +27,6 @@ fn main() {
     let path = args()
         .nth(1)
         .unwrap_or_else(|| "myLargeFile.bin".to_string());
-    
+
     chunk_file(path).unwrap();
 }
@@ -1,6 +1,6 @@
 //! This example demonstrates how to use the `NodeIter` iterator to build a tree from a list of hashed chunks.
 
-use rustic_cdc::{HashedChunk, Node, NodeIter};
+use rustic_cdc::{Child, HashedChunk, Node, NodeIter};
 
 type IntHash = u32;
 
@@ -20,12 +20,8 @@ fn get_new_hash_id() -> IntHash {
 }
 
 #[allow(clippy::ptr_arg)]
-fn my_new_node(level: usize, children: &Vec<IntHash>) -> Node<IntHash> {
-    Node {
-        hash: get_new_hash_id(),
-        level,
-        children: children.clone(),
-    }
+fn my_new_node(level: usize, children: &Vec<Child<IntHash>>) -> Node<IntHash> {
+    Node::new(get_new_hash_id(), level, children.clone())
 }
 
 fn main() {
@@ -34,11 +30,14 @@ fn main() {
     let hashed_chunk_it = levels.iter().enumerate().map(|(index, level)| HashedChunk {
         hash: IntHash::try_from(index).unwrap(),
         level: *level,
+        size: 1,
     });
 
     HASH_ID.set(IntHash::try_from(levels.len()).unwrap());
 
-    for node in NodeIter::new(hashed_chunk_it, my_new_node, 0) {
+    let mut node_iter = NodeIter::new(hashed_chunk_it, my_new_node, 0);
+    for node in node_iter.by_ref() {
         println!("{node:?}");
     }
+    println!("Root: {:?}", node_iter.root());
 }
@@ -15,7 +15,9 @@ use std::{
 };
 
 use ring::digest;
-use rustic_cdc::{Chunk, ChunkIter, HashToLevel, HashedChunk, Node, NodeIter, SeparatorIter};
+use rustic_cdc::{
+    Child, Chunk, ChunkIter, HashToLevel, HashedChunk, Node, NodeIter, SeparatorIter,
+};
 
 type Hash256 = [u8; 256 / 8];
 
@@ -65,20 +67,16 @@ fn file_chunk_reader<S: Into<String>>(
     Ok(BufReader::new(file).take(chunk.size))
 }
 
-fn new_hash_node(level: usize, children: &Vec<Hash256>) -> Node<Hash256> {
+fn new_hash_node(level: usize, children: &Vec<Child<Hash256>>) -> Node<Hash256> {
     let mut ctx = digest::Context::new(&digest::SHA256);
     ctx.update(&[1u8]); // To mark that it is a node, not a chunk.
     for child in children {
-        ctx.update(child);
+        ctx.update(&child.hash);
     }
     let digest = ctx.finish();
     let hash: Hash256 = *array_ref![digest.as_ref(), 0, 256 / 8];
 
-    Node {
-        hash,
-        level,
-        children: children.clone(),
-    }
+    Node::new(hash, level, children.clone())
 }
 
 fn chunk_file<S: Into<String>>(path: S) -> io::Result<()> {
@@ -109,7 +107,11 @@ fn chunk_file<S: Into<String>>(path: S) -> io::Result<()> {
         // Calculates the level of the separators.
         let level = HashToLevel::custom_new(13, 3).to_level(chunk.separator_hash);
 
-        HashedChunk { hash, level }
+        HashedChunk {
+            hash,
+            level,
+            size: chunk.size,
+        }
     });
 
     // Builds a tree of hash nodes.
@@ -0,0 +1,137 @@
+use crate::Separator;
+
+/// An iterator that separates data with the Asymmetric Extremum (AE) algorithm.
+///
+/// A chunk is cut after the byte at `window_size` bytes from the first maximum byte value of
+/// the chunk, when no byte in between is greater: the maximum is extreme in a fixed-size window
+/// on its right only. The expected chunk size is about `(e - 1) * window_size`, and a chunk is
+/// never smaller than `window_size + 1` bytes, except the last one.
+///
+/// No hash is involved, the hash of the separators is always `0`: when collecting
+/// [`ChunkStats`](crate::ChunkStats), use a predicate accepting any hash, e.g. `|_| true`.
+#[derive(Debug)]
+pub struct AeSeparatorIter<I> {
+    /// The iterator to separate.
+    iter: I,
+
+    /// The size of the window on the right of the maximum.
+    window_size: u64,
+
+    /// The number of bytes read.
+    index: u64,
+
+    /// The maximum byte value of the current chunk and its index, `None` at the start of a chunk.
+    max: Option<(u8, u64)>,
+}
+
+impl<I> AeSeparatorIter<I>
+where
+    I: Iterator<Item = u8>,
+{
+    /// Creates a new `AeSeparatorIter`, with an expected chunk size of about 8 KiB.
+    ///
+    /// # Arguments
+    ///
+    /// * `iter` - The iterator to separate.
+    pub fn new(iter: I) -> Self {
+        // 8192 / (e - 1)
+        Self::custom_new(iter, 4_768)
+    }
+
+    /// Creates a new `AeSeparatorIter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `iter` - The iterator to separate.
+    /// * `window_size` - The size of the window on the right of the maximum.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window_size` is `0`.
+    pub fn custom_new(iter: I, window_size: u64) -> Self {
+        assert!(window_size > 0, "the window size must not be 0");
+        Self {
+            iter,
+            window_size,
+            index: 0,
+            max: None,
+        }
+    }
+}
+
+impl<I> Iterator for AeSeparatorIter<I>
+where
+    I: Iterator<Item = u8>,
+{
+    type Item = Separator;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        for byte in self.iter.by_ref() {
+            let position = self.index;
+            self.index += 1;
+            match self.max {
+                Some((max_value, max_position)) if byte <= max_value => {
+                    if position == max_position + self.window_size {
+                        self.max = None;
+                        return Some(Separator {
+                            index: self.index,
+                            hash: 0,
+                        });
+                    }
+                }
+                _ => self.max = Some((byte, position)),
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::pseudo_random;
+    use crate::*;
+
+    /// Returns the size of the first chunk of `data`, as described in the AE paper.
+    fn reference_chunk_size(data: &[u8], window_size: usize) -> usize {
+        let mut max_position = 0;
+        for position in 1..data.len() {
+            if data[position] <= data[max_position] {
+                if position == max_position + window_size {
+                    return position + 1;
+                }
+            } else {
+                max_position = position;
+            }
+        }
+        data.len()
+    }
+
+    #[test]
+    fn ae_separators() {
+        let data = pseudo_random(200_000);
+        let window_size = 256;
+
+        let separators = AeSeparatorIter::custom_new(data.iter().copied(), window_size as u64);
+        let chunks: Vec<Chunk> = ChunkIter::new(separators, data.len() as u64).collect();
+        assert!(chunks.len() > 100);
+
+        let mut stats = ChunkStats::new(|_| true);
+        let mut offset = 0;
+        for chunk in &chunks {
+            stats.add(chunk);
+            let size = reference_chunk_size(&data[offset..], window_size);
+            assert_eq!(chunk.size, size as u64);
+            assert_eq!(chunk.separator_hash, 0);
+            offset += size;
+            assert!(size > window_size || offset == data.len());
+        }
+        assert_eq!(offset, data.len());
+
+        stats.end_stream();
+        let summary = stats.summary();
+        // The expected size is about (e - 1) * window_size.
+        assert!((300.0..600.0).contains(&summary.mean));
+    }
+}
@@ -0,0 +1,145 @@
+use std::io::{self, BufWriter, Read, Write};
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+use sha2::{Digest, Sha256};
+
+use crate::{open_input, ParamsArgs};
+
+/// The format of the chunk boundaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// One JSON object per line.
+    Jsonl,
+
+    /// Comma-separated values, with a header line.
+    Csv,
+}
+
+/// The arguments of the `chunk` command.
+#[derive(Debug, Args)]
+pub struct ChunkArgs {
+    /// The file to chunk, stdin if missing or `-`.
+    path: Option<PathBuf>,
+
+    /// The output format.
+    #[arg(long, value_enum, default_value_t = Format::Jsonl)]
+    format: Format,
+
+    /// The chunker parameters.
+    #[command(flatten)]
+    params: ParamsArgs,
+}
+
+/// Chunks a file, and writes the offset, length, separator hash, level and SHA-256 digest
+/// of each chunk.
+pub fn run(args: &ChunkArgs) -> io::Result<()> {
+    let input = open_input(args.path.as_ref())?;
+    let mut out = BufWriter::new(io::stdout().lock());
+    write_chunks(args, input, &mut out)?;
+    out.flush()
+}
+
+/// Chunks data, and writes its chunks in the format of the arguments.
+fn write_chunks(args: &ChunkArgs, input: impl Read, out: &mut impl Write) -> io::Result<()> {
+    let params = args.params.params()?;
+    let hash_to_level = params.hash_to_level();
+
+    if args.format == Format::Csv {
+        writeln!(out, "offset,length,separator_hash,level,digest")?;
+    }
+    for chunk in params.chunk_data(input) {
+        let (chunk, data) = chunk?;
+        let offset = chunk.index - chunk.size;
+        let level = hash_to_level.to_level(chunk.separator_hash);
+        let digest = Sha256::digest(&data);
+        match args.format {
+            Format::Jsonl => writeln!(
+                out,
+                r#"{{"offset":{offset},"length":{},"separator_hash":"{:016x}","level":{level},"digest":"{digest:x}"}}"#,
+                chunk.size, chunk.separator_hash
+            )?,
+            Format::Csv => writeln!(
+                out,
+                "{offset},{},{:016x},{level},{digest:x}",
+                chunk.size, chunk.separator_hash
+            )?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use rustic_cdc::ChunkerParams;
+
+    use super::*;
+    use crate::tests::pseudo_random;
+    use crate::{Cli, Command};
+
+    /// Returns the output of the `chunk` command on some data, with the given arguments.
+    fn chunk_output(args: &[&str], data: &[u8]) -> String {
+        let cli = Cli::try_parse_from([&["cdc", "chunk"], args].concat()).unwrap();
+        let Command::Chunk(args) = cli.command else {
+            unreachable!()
+        };
+        let mut out = vec![];
+        write_chunks(&args, data, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn jsonl_output() {
+        let data = pseudo_random(20_000);
+        let output = chunk_output(&["--boundary-nb-bits", "10"], &data);
+
+        let params = ChunkerParams {
+            boundary_nb_bits: 10,
+            ..ChunkerParams::default()
+        };
+        let chunks: Vec<_> = params.chunk_data(&data[..]).map(Result::unwrap).collect();
+        assert!(chunks.len() > 5);
+        assert_eq!(output.lines().count(), chunks.len());
+
+        for (line, (chunk, chunk_data)) in output.lines().zip(&chunks) {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(value["offset"], chunk.index - chunk.size);
+            assert_eq!(value["length"], chunk.size);
+            let separator_hash = format!("{:016x}", chunk.separator_hash);
+            assert_eq!(value["separator_hash"], separator_hash.as_str());
+            let level = params.hash_to_level().to_level(chunk.separator_hash);
+            assert_eq!(value["level"], level);
+            let digest = format!("{:x}", Sha256::digest(chunk_data));
+            assert_eq!(value["digest"], digest.as_str());
+        }
+    }
+
+    #[test]
+    fn csv_output() {
+        assert_eq!(
+            chunk_output(&["--format", "csv"], b""),
+            "offset,length,separator_hash,level,digest\n"
+        );
+        assert_eq!(
+            chunk_output(&["--format", "csv"], b"abc"),
+            "offset,length,separator_hash,level,digest\n\
+             0,3,0000000000000000,0,\
+             ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\n"
+        );
+
+        let data = pseudo_random(20_000);
+        let output = chunk_output(&["--format", "csv", "--boundary-nb-bits", "10"], &data);
+        let mut offset = 0;
+        for line in output.lines().skip(1) {
+            let fields: Vec<&str> = line.split(',').collect();
+            assert_eq!(fields.len(), 5);
+            assert_eq!(fields[0].parse::<u64>().unwrap(), offset);
+            offset += fields[1].parse::<u64>().unwrap();
+            assert_eq!(fields[2].len(), 16);
+            assert_eq!(fields[4].len(), 64);
+        }
+        assert_eq!(offset, 20_000);
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use rustic_cdc::{ChunkStats, ChunkerParams};
+use sha2::{Digest, Sha256};
+
+use crate::ParamsArgs;
+
+/// The arguments of the `dedup` command.
+#[derive(Debug, Args)]
+pub struct DedupArgs {
+    /// The files and directories to analyze, directories being walked recursively.
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+
+    /// The number of most duplicated chunks to report.
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+
+    /// The chunker parameters.
+    #[command(flatten)]
+    params: ParamsArgs,
+}
+
+/// A distinct chunk found while analyzing.
+#[derive(Debug, Clone, Copy)]
+struct ChunkCount {
+    /// The size of the chunk.
+    size: u64,
+
+    /// The number of times the chunk was found.
+    count: u64,
+}
+
+/// The deduplication analysis of a dataset.
+#[derive(Debug)]
+struct Analysis<P> {
+    /// The distinct chunks, indexed by their SHA-256 digest.
+    chunks: HashMap<[u8; 32], ChunkCount>,
+
+    /// The statistics of all the chunks, duplicates included.
+    stats: ChunkStats<P>,
+}
+
+impl<P: Fn(u64) -> bool> Analysis<P> {
+    /// Creates a new, empty analysis.
+    fn new(predicate: P) -> Self {
+        Self {
+            chunks: HashMap::new(),
+            stats: ChunkStats::new(predicate),
+        }
+    }
+
+    /// Chunks a file or, recursively, the files of a directory. Symbolic links are not followed.
+    fn add_path(&mut self, params: &ChunkerParams, path: &Path) -> io::Result<()> {
+        let file_type = fs::symlink_metadata(path)?.file_type();
+        if file_type.is_dir() {
+            for entry in fs::read_dir(path)? {
+                self.add_path(params, &entry?.path())?;
+            }
+        } else if file_type.is_file() {
+            self.add_file(params, path)?;
+        }
+        Ok(())
+    }
+
+    /// Chunks a file.
+    fn add_file(&mut self, params: &ChunkerParams, path: &Path) -> io::Result<()> {
+        for chunk in params.chunk_data(File::open(path)?) {
+            let (chunk, data) = chunk?;
+            self.stats.add(&chunk);
+            self.chunks
+                .entry(Sha256::digest(&data).into())
+                .or_insert(ChunkCount {
+                    size: chunk.size,
+                    count: 0,
+                })
+                .count += 1;
+        }
+        self.stats.end_stream();
+        Ok(())
+    }
+
+    /// Writes the report of the analysis.
+    #[allow(clippy::cast_precision_loss)] // The ratio doesn't need to be exact.
+    fn write_report(&self, out: &mut impl Write, top: usize) -> io::Result<()> {
+        let summary = self.stats.summary();
+        let unique_bytes: u64 = self.chunks.values().map(|chunk| chunk.size).sum();
+        let ratio = if unique_bytes == 0 {
+            1.0
+        } else {
+            summary.total_bytes as f64 / unique_bytes as f64
+        };
+        writeln!(out, "Files:         {}", summary.nb_streams)?;
+        writeln!(
+            out,
+            "Chunks:        {} ({} unique)",
+            summary.nb_chunks,
+            self.chunks.len()
+        )?;
+        writeln!(out, "Total bytes:   {}", summary.total_bytes)?;
+        writeln!(out, "Unique bytes:  {unique_bytes}")?;
+        writeln!(out, "Dedup ratio:   {ratio:.3}")?;
+
+        writeln!(
+            out,
+            "\nChunk sizes:   mean {:.0}, std dev {:.0}, median {}, p90 {}, p99 {}",
+            summary.mean,
+            summary.std_dev(),
+            summary.p50,
+            summary.p90,
+            summary.p99
+        )?;
+        writeln!(
+            out,
+            "Forced cuts:   {:.2}%",
+            summary.forced_cut_ratio * 100.0
+        )?;
+        for (size, count) in &summary.histogram {
+            let range = format!("{size}..{}", u128::from(*size) * 2);
+            writeln!(out, "  {range:>21} bytes: {count}")?;
+        }
+
+        let mut duplicated: Vec<_> = self
+            .chunks
+            .iter()
+            .filter(|(_, chunk)| chunk.count > 1)
+            .collect();
+        // The most duplicated chunks are the ones saving the most bytes.
+        duplicated.sort_by_key(|(_, chunk)| std::cmp::Reverse((chunk.count - 1) * chunk.size));
+        if !duplicated.is_empty() {
+            writeln!(out, "\nMost duplicated chunks:")?;
+        }
+        for (digest, chunk) in duplicated.into_iter().take(top) {
+            write!(out, "  ")?;
+            for byte in digest {
+                write!(out, "{byte:02x}")?;
+            }
+            writeln!(
+                out,
+                " size: {:8}, count: {:6}, saved: {} bytes",
+                chunk.size,
+                chunk.count,
+                (chunk.count - 1) * chunk.size
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Chunks all the files of the given paths, and reports how well they deduplicate.
+pub fn run(args: &DedupArgs) -> io::Result<()> {
+    let params = args.params.params()?;
+    let mut analysis = Analysis::new(params.predicate());
+    for path in &args.paths {
+        analysis.add_path(&params, path)?;
+    }
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    analysis.write_report(&mut out, args.top)?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::pseudo_random;
+
+    #[test]
+    fn report() {
+        let root = std::env::temp_dir().join(format!("cdc-dedup-{}", std::process::id()));
+        fs::create_dir_all(root.join("sub")).unwrap();
+        let data = pseudo_random(30_000);
+        fs::write(root.join("first"), &data).unwrap();
+        fs::write(root.join("sub").join("copy"), &data).unwrap();
+        fs::write(root.join("sub").join("other"), &data[..10_000]).unwrap();
+
+        let params = ChunkerParams {
+            boundary_nb_bits: 10,
+            ..ChunkerParams::default()
+        };
+        let mut analysis = Analysis::new(params.predicate());
+        analysis.add_path(&params, &root).unwrap();
+        let mut out = vec![];
+        analysis.write_report(&mut out, 2).unwrap();
+        fs::remove_dir_all(root).unwrap();
+
+        let report = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = report.lines().collect();
+        let nb_chunks =
+            2 * params.chunk_data(&data[..]).count() + params.chunk_data(&data[..10_000]).count();
+        let unique_chunks = analysis.chunks.len();
+        let unique_bytes: u64 = analysis.chunks.values().map(|chunk| chunk.size).sum();
+        assert!(unique_bytes < 40_000);
+        assert_eq!(lines[0], "Files:         3");
+        assert_eq!(
+            lines[1],
+            format!("Chunks:        {nb_chunks} ({unique_chunks} unique)")
+        );
+        assert_eq!(lines[2], "Total bytes:   70000");
+        assert_eq!(lines[3], format!("Unique bytes:  {unique_bytes}"));
+        assert_eq!(
+            lines[4],
+            format!(
+                "Dedup ratio:   {:.3}",
+                70_000.0 / f64::from(u32::try_from(unique_bytes).unwrap())
+            )
+        );
+        assert!(lines[6].starts_with("Chunk sizes:   mean "));
+        assert!(lines[7].starts_with("Forced cuts:   "));
+        let top = lines
+            .iter()
+            .position(|line| *line == "Most duplicated chunks:")
+            .unwrap();
+        assert!(lines[8..top - 1]
+            .iter()
+            .all(|line| line.contains(" bytes: ")));
+        assert_eq!(lines.len(), top + 3);
+        for line in &lines[top + 1..] {
+            let (digest, counts) = line.trim_start().split_once(' ').unwrap();
+            assert_eq!(digest.len(), 64);
+            assert!(counts.starts_with("size: "));
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! `cdc`, a command-line tool to inspect the content-defined chunking of files.
+
+mod chunk;
+mod dedup;
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand};
+use rustic_cdc::ChunkerParams;
+
+/// Inspects the content-defined chunking of files.
+#[derive(Debug, Parser)]
+#[command(name = "cdc", version, about)]
+struct Cli {
+    /// The command to run.
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// A command of the tool.
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Chunks a file or stdin, and outputs the boundaries of its chunks.
+    Chunk(chunk::ChunkArgs),
+
+    /// Chunks files and directories, and reports how well they deduplicate.
+    Dedup(dedup::DedupArgs),
+}
+
+/// The chunker parameters, see [`ChunkerParams`].
+#[derive(Debug, Clone, Copy, Args)]
+struct ParamsArgs {
+    /// The number of bits of the rolling hash window size.
+    #[arg(long, default_value_t = 6, value_parser = clap::value_parser!(u32).range(1..32))]
+    separator_size_nb_bits: u32,
+
+    /// The number of bits of the hash checked to find a boundary, the expected chunk size being
+    /// 2^bits bytes.
+    #[arg(long, default_value_t = 13, value_parser = clap::value_parser!(u32).range(1..64))]
+    boundary_nb_bits: u32,
+
+    /// The number of bits of the level 0.
+    #[arg(long, default_value_t = 13, value_parser = clap::value_parser!(u32).range(0..64))]
+    lvl0_nb_bits: u32,
+
+    /// The number of bits of the level up.
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..64))]
+    lvlup_nb_bits: u32,
+
+    /// The maximum number of children of a node, 0 for no limit.
+    #[arg(long, default_value_t = 0)]
+    max_node_children: usize,
+}
+
+impl ParamsArgs {
+    /// Returns the chunker parameters.
+    fn params(self) -> io::Result<ChunkerParams> {
+        let params = ChunkerParams {
+            separator_size_nb_bits: self.separator_size_nb_bits,
+            boundary_nb_bits: self.boundary_nb_bits,
+            lvl0_nb_bits: self.lvl0_nb_bits,
+            lvlup_nb_bits: self.lvlup_nb_bits,
+            max_node_children: self.max_node_children,
+        };
+        if !params.is_valid() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid chunker parameters",
+            ));
+        }
+        Ok(params)
+    }
+}
+
+/// Opens a file, or stdin if the path is `-` or missing.
+fn open_input(path: Option<&PathBuf>) -> io::Result<Box<dyn Read>> {
+    match path {
+        Some(path) if path.as_os_str() != "-" => Ok(Box::new(File::open(path)?)),
+        _ => Ok(Box::new(io::stdin().lock())),
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match &cli.command {
+        Command::Chunk(args) => chunk::run(args),
+        Command::Dedup(args) => dedup::run(args),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        // A closed output, like when piping into `head`, is not an error.
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("cdc: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    /// Returns pseudo-random data for the tests of the commands.
+    pub(crate) fn pseudo_random(len: u32) -> Vec<u8> {
+        (0..len)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13).to_le_bytes()[0])
+            .collect()
+    }
+
+    /// A parser of the chunker parameters alone.
+    #[derive(Debug, Parser)]
+    struct ParamsCli {
+        #[command(flatten)]
+        params: ParamsArgs,
+    }
+
+    /// Returns the chunker parameters parsed from arguments.
+    fn parse_params(args: &[&str]) -> Result<io::Result<ChunkerParams>, clap::Error> {
+        let cli = ParamsCli::try_parse_from([&["cdc"], args].concat())?;
+        Ok(cli.params.params())
+    }
+
+    #[test]
+    fn arguments() {
+        Cli::command().debug_assert();
+
+        assert_eq!(
+            parse_params(&[]).unwrap().unwrap(),
+            ChunkerParams::default()
+        );
+        let args = [
+            "--separator-size-nb-bits",
+            "5",
+            "--boundary-nb-bits",
+            "12",
+            "--max-node-children",
+            "8",
+        ];
+        let expected = ChunkerParams {
+            separator_size_nb_bits: 5,
+            boundary_nb_bits: 12,
+            max_node_children: 8,
+            ..ChunkerParams::default()
+        };
+        assert_eq!(parse_params(&args).unwrap().unwrap(), expected);
+
+        for args in [
+            ["--separator-size-nb-bits", "32"],
+            ["--boundary-nb-bits", "0"],
+            ["--lvl0-nb-bits", "64"],
+            ["--lvlup-nb-bits", "64"],
+        ] {
+            assert!(parse_params(&args).is_err(), "{args:?}");
+        }
+        // Only checked once parsed.
+        let err = parse_params(&["--max-node-children", "1"])
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        assert!(matches!(
+            Cli::try_parse_from(["cdc", "chunk", "-"]).unwrap().command,
+            Command::Chunk(_)
+        ));
+        assert!(matches!(
+            Cli::try_parse_from(["cdc", "dedup", "a", "b"])
+                .unwrap()
+                .command,
+            Command::Dedup(_)
+        ));
+        for args in [
+            &["cdc"][..],
+            &["cdc", "dedup"],
+            &["cdc", "chunk", "--format", "xml"],
+            &["cdc", "chunk", "a", "b"],
+        ] {
+            assert!(Cli::try_parse_from(args).is_err(), "{args:?}");
+        }
+    }
+}
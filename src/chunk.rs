@@ -1,7 +1,8 @@
 use crate::Separator;
 
 /// A chunk is a part of a stream of data that is separated by a separator.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chunk {
     /// The index of the chunk in the stream.
     pub index: u64,
@@ -69,3 +70,20 @@ impl<Iter: Iterator<Item = Separator>> Iterator for ChunkIter<Iter> {
         }
     }
 }
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn serde_round_trip() {
+        let chunk = Chunk {
+            index: 40,
+            size: 40,
+            separator_hash: 0xff,
+        };
+        let json = serde_json::to_string(&chunk).unwrap();
+        assert_eq!(json, r#"{"index":40,"size":40,"separator_hash":255}"#);
+        assert_eq!(serde_json::from_str::<Chunk>(&json).unwrap(), chunk);
+    }
+}
@@ -0,0 +1,168 @@
+use std::io::{self, BufReader, Bytes, Read};
+
+use crate::{BoundaryPredicate, Chunk, SeparatorIter};
+
+/// A byte iterator over a reader, keeping the bytes read in a buffer.
+#[derive(Debug)]
+pub(crate) struct BufferedBytes<R> {
+    /// The bytes of the reader.
+    bytes: Bytes<BufReader<R>>,
+
+    /// The bytes read and not consumed yet.
+    buffer: Vec<u8>,
+
+    /// The first error encountered while reading, which ends the iteration.
+    error: Option<io::Error>,
+}
+
+impl<R: Read> BufferedBytes<R> {
+    /// Creates a new `BufferedBytes`.
+    fn new(reader: R) -> Self {
+        Self {
+            bytes: BufReader::new(reader).bytes(),
+            buffer: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+impl<R: Read> Iterator for BufferedBytes<R> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+        match self.bytes.next()? {
+            Ok(byte) => {
+                self.buffer.push(byte);
+                Some(byte)
+            }
+            Err(err) => {
+                self.error = Some(err);
+                None
+            }
+        }
+    }
+}
+
+/// An iterator that chunks the data of a reader, and yields each chunk with its data.
+#[derive(Debug)]
+pub struct ChunkDataIter<R, F> {
+    /// The separators of the data.
+    separators: SeparatorIter<BufferedBytes<R>, F>,
+
+    /// The index of the last separator.
+    last_separator_index: u64,
+}
+
+impl<R: Read> ChunkDataIter<R, fn(u64) -> bool> {
+    /// Creates a new `ChunkDataIter`, finding the same separators as [`SeparatorIter::new`].
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader of the data to chunk.
+    pub fn new(reader: R) -> Self {
+        Self::with_separators(SeparatorIter::new(BufferedBytes::new(reader)))
+    }
+}
+
+impl<R, F> ChunkDataIter<R, F>
+where
+    R: Read,
+    F: BoundaryPredicate,
+{
+    /// Creates a new `ChunkDataIter`, finding the same separators as [`SeparatorIter::custom_new`].
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader of the data to chunk.
+    /// * `separator_size_nb_bits` - The number of bits of the separator size.
+    /// * `predicate` - The predicate used to determine if a separator is a separator boundary.
+    pub fn custom_new(reader: R, separator_size_nb_bits: u32, predicate: F) -> Self {
+        Self::with_separators(SeparatorIter::custom_new(
+            BufferedBytes::new(reader),
+            separator_size_nb_bits,
+            predicate,
+        ))
+    }
+
+    /// Creates a new `ChunkDataIter` from the separators of the data.
+    fn with_separators(separators: SeparatorIter<BufferedBytes<R>, F>) -> Self {
+        Self {
+            separators,
+            last_separator_index: 0,
+        }
+    }
+}
+
+impl<R, F> Iterator for ChunkDataIter<R, F>
+where
+    R: Read,
+    F: BoundaryPredicate,
+{
+    type Item = io::Result<(Chunk, Vec<u8>)>;
+
+    // The chunk is in the buffer, so its size fits in a `usize`.
+    #[allow(clippy::cast_possible_truncation)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let separator = self.separators.next();
+        let bytes = self.separators.inner_mut();
+        if let Some(err) = bytes.error.take() {
+            return Some(Err(err));
+        }
+
+        // The separator iterator reads ahead, the buffer may contain the start of the next chunk.
+        let (chunk, data) = if let Some(separator) = separator {
+            let size = separator.index - self.last_separator_index;
+            let data = bytes.buffer.drain(..size as usize).collect();
+            let chunk = Chunk {
+                index: separator.index,
+                size,
+                separator_hash: separator.hash,
+            };
+            (chunk, data)
+        } else if !bytes.buffer.is_empty() {
+            let data = std::mem::take(&mut bytes.buffer);
+            let chunk = Chunk {
+                index: self.last_separator_index + data.len() as u64,
+                size: data.len() as u64,
+                separator_hash: 0, // any value is ok, last chunk of the stream.
+            };
+            (chunk, data)
+        } else {
+            return None;
+        };
+        self.last_separator_index = chunk.index;
+
+        Some(Ok((chunk, data)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::periodic_data;
+    use crate::*;
+
+    #[test]
+    fn chunk_data() {
+        let data = periodic_data(50_000);
+        let predicate = |x: u64| x & 0xff == 0xff;
+
+        let separators = SeparatorIter::custom_new(data.iter().copied(), 4, predicate);
+        let expected: Vec<Chunk> = ChunkIter::new(separators, data.len() as u64).collect();
+        assert!(expected.len() > 10);
+
+        let chunks: Vec<(Chunk, Vec<u8>)> = ChunkDataIter::custom_new(&data[..], 4, predicate)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(chunks.len(), expected.len());
+        let mut offset = 0;
+        for ((chunk, chunk_data), expected) in chunks.iter().zip(&expected) {
+            assert_eq!(chunk, expected);
+            assert_eq!(chunk_data[..], data[offset..offset + chunk_data.len()]);
+            offset += chunk_data.len();
+        }
+        assert_eq!(offset, data.len());
+    }
+}
@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs::{self, OpenOptions};
+use std::hash::Hash;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::Chunk;
+
+/// A content-addressed storage for chunks, indexed by the hash of their data.
+pub trait ChunkStore<H> {
+    /// Returns `true` if the chunk with the given hash is in the store.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the chunk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store can't be accessed.
+    fn contains(&self, hash: &H) -> io::Result<bool>;
+
+    /// Returns the data of the chunk with the given hash, if it is in the store.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the chunk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store can't be accessed.
+    fn get(&self, hash: &H) -> io::Result<Option<Vec<u8>>>;
+
+    /// Puts a chunk in the store, if it is not already in it.
+    ///
+    /// Returns `true` if the chunk was new.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the chunk.
+    /// * `data` - The data of the chunk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store can't be accessed.
+    fn put(&mut self, hash: &H, data: &[u8]) -> io::Result<bool>;
+}
+
+/// A `ChunkStore` whose chunks can be listed and removed, to collect garbage.
+pub trait PrunableChunkStore<H>: ChunkStore<H> {
+    /// Returns the hashes of all the chunks in the store.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store can't be accessed.
+    fn hashes(&self) -> io::Result<Vec<H>>;
+
+    /// Removes chunks from the store. The hashes which are not in the store are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `hashes` - The hashes of the chunks to remove.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store can't be accessed.
+    fn remove(&mut self, hashes: &[H]) -> io::Result<()>;
+}
+
+/// A `ChunkStore` keeping the chunks in memory.
+#[derive(Debug, Clone)]
+pub struct MemoryChunkStore<H> {
+    /// The data of the chunks, indexed by their hash.
+    chunks: HashMap<H, Vec<u8>>,
+}
+
+impl<H> Default for MemoryChunkStore<H> {
+    fn default() -> Self {
+        Self {
+            chunks: HashMap::new(),
+        }
+    }
+}
+
+impl<H> MemoryChunkStore<H> {
+    /// Creates a new, empty `MemoryChunkStore`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of chunks in the store.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Returns `true` if the store contains no chunk.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+}
+
+impl<H: Eq + Hash + Clone> ChunkStore<H> for MemoryChunkStore<H> {
+    fn contains(&self, hash: &H) -> io::Result<bool> {
+        Ok(self.chunks.contains_key(hash))
+    }
+
+    fn get(&self, hash: &H) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.chunks.get(hash).cloned())
+    }
+
+    fn put(&mut self, hash: &H, data: &[u8]) -> io::Result<bool> {
+        if self.chunks.contains_key(hash) {
+            return Ok(false);
+        }
+        _ = self.chunks.insert(hash.clone(), data.to_vec());
+        Ok(true)
+    }
+}
+
+impl<H: Eq + Hash + Clone> PrunableChunkStore<H> for MemoryChunkStore<H> {
+    fn hashes(&self) -> io::Result<Vec<H>> {
+        Ok(self.chunks.keys().cloned().collect())
+    }
+
+    fn remove(&mut self, hashes: &[H]) -> io::Result<()> {
+        for hash in hashes {
+            _ = self.chunks.remove(hash);
+        }
+        Ok(())
+    }
+}
+
+/// A `ChunkStore` keeping each chunk in a file of a local directory.
+///
+/// A chunk is stored in `<root>/<xx>/<hash>`, where `<hash>` is its hash in hexadecimal
+/// and `<xx>` the first byte of it, to avoid directories with too many files.
+#[derive(Debug, Clone)]
+pub struct DirChunkStore {
+    /// The root directory of the store.
+    root: PathBuf,
+}
+
+impl DirChunkStore {
+    /// Opens a `DirChunkStore`, creating its root directory if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The root directory of the store.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the root directory can't be created.
+    pub fn new(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+        Ok(Self { root })
+    }
+
+    /// Returns the path of the file of a chunk.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the chunk.
+    #[must_use]
+    pub fn chunk_path(&self, hash: &[u8]) -> PathBuf {
+        let hex = to_hex(hash);
+        self.root.join(hex.get(..2).unwrap_or("00")).join(hex)
+    }
+}
+
+impl<H: AsRef<[u8]>> ChunkStore<H> for DirChunkStore {
+    fn contains(&self, hash: &H) -> io::Result<bool> {
+        self.chunk_path(hash.as_ref()).try_exists()
+    }
+
+    fn get(&self, hash: &H) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.chunk_path(hash.as_ref())) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn put(&mut self, hash: &H, data: &[u8]) -> io::Result<bool> {
+        let path = self.chunk_path(hash.as_ref());
+        if path.try_exists()? {
+            return Ok(false);
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        write_file(&path, data)?;
+        Ok(true)
+    }
+}
+
+impl<H> PrunableChunkStore<H> for DirChunkStore
+where
+    H: for<'a> TryFrom<&'a [u8]> + AsRef<[u8]>,
+{
+    fn hashes(&self) -> io::Result<Vec<H>> {
+        let mut hashes = vec![];
+        for dir in fs::read_dir(&self.root)? {
+            let dir = dir?;
+            if !dir.file_type()?.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(dir.path())? {
+                // Other files, like temporary files, are not chunks.
+                let file_name = file?.file_name();
+                let hash = file_name
+                    .to_str()
+                    .and_then(from_hex)
+                    .and_then(|bytes| H::try_from(&bytes).ok());
+                if let Some(hash) = hash {
+                    hashes.push(hash);
+                }
+            }
+        }
+        Ok(hashes)
+    }
+
+    fn remove(&mut self, hashes: &[H]) -> io::Result<()> {
+        for hash in hashes {
+            match fs::remove_file(self.chunk_path(hash.as_ref())) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes a file through a temporary file, renamed once its data is synced to the disk, so
+/// that the file is never partially written.
+///
+/// The name of the temporary file is unique to the process and the call, so that concurrent
+/// writers of the same file don't clobber each other's temporary files.
+pub(crate) fn write_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    static NB_TMP_FILES: AtomicU64 = AtomicU64::new(0);
+
+    let nb_tmp_files = NB_TMP_FILES.fetch_add(1, Ordering::Relaxed);
+    let tmp_path = path.with_extension(format!("{}-{nb_tmp_files}.tmp", std::process::id()));
+    let result = write_synced(&tmp_path, data).and_then(|()| fs::rename(&tmp_path, path));
+    if result.is_err() {
+        _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Writes a new file and syncs its data to the disk.
+fn write_synced(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+/// Returns the bytes in lowercase hexadecimal.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// Returns the bytes written in hexadecimal, or `None` if it is not valid hexadecimal.
+pub(crate) fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| {
+            let byte = hex.get(index..index + 2)?;
+            u8::from_str_radix(byte, 16).ok()
+        })
+        .collect()
+}
+
+/// Statistics about chunks put in a store.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StoreStats {
+    /// The number of chunks which were new.
+    pub new_chunks: u64,
+
+    /// The total size of the chunks which were new.
+    pub new_bytes: u64,
+
+    /// The number of chunks which were already in the store.
+    pub dedup_chunks: u64,
+
+    /// The total size of the chunks which were already in the store.
+    pub dedup_bytes: u64,
+}
+
+/// Hashes chunks and puts them in a store.
+///
+/// Returns the chunks with their hash, and statistics about deduplication.
+///
+/// # Arguments
+///
+/// * `store` - The store to put the chunks in.
+/// * `chunks` - The chunks with their data, usually a [`ChunkDataIter`](crate::ChunkDataIter).
+/// * `hash` - The function calculating the hash of the data of a chunk.
+///
+/// # Errors
+///
+/// Returns the first error from `chunks` or from the store.
+pub fn store_chunks<S, H, I, F>(
+    store: &mut S,
+    chunks: I,
+    hash: F,
+) -> io::Result<(Vec<(Chunk, H)>, StoreStats)>
+where
+    S: ChunkStore<H>,
+    I: IntoIterator<Item = io::Result<(Chunk, Vec<u8>)>>,
+    F: Fn(&[u8]) -> H,
+{
+    let mut hashed_chunks = vec![];
+    let mut stats = StoreStats::default();
+    for chunk in chunks {
+        let (chunk, data) = chunk?;
+        let chunk_hash = hash(&data);
+        if store.put(&chunk_hash, &data)? {
+            stats.new_chunks += 1;
+            stats.new_bytes += chunk.size;
+        } else {
+            stats.dedup_chunks += 1;
+            stats.dedup_bytes += chunk.size;
+        }
+        hashed_chunks.push((chunk, chunk_hash));
+    }
+
+    Ok((hashed_chunks, stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{hash, periodic_data, pseudo_random};
+    use crate::*;
+
+    fn check_store(store: &mut impl ChunkStore<[u8; 8]>) {
+        let data = periodic_data(20_000);
+        let twice = [&data[..], &data[..]].concat();
+        let chunks = ChunkDataIter::custom_new(&twice[..], 4, |x| x & 0xff == 0xff);
+
+        let (hashed_chunks, stats) = store_chunks(store, chunks, hash).unwrap();
+        assert_eq!(stats.new_bytes + stats.dedup_bytes, twice.len() as u64);
+        assert!(stats.dedup_bytes > data.len() as u64 / 2);
+
+        for (chunk, chunk_hash) in hashed_chunks {
+            assert!(store.contains(&chunk_hash).unwrap());
+            let stored = store.get(&chunk_hash).unwrap().unwrap();
+            assert_eq!(stored.len() as u64, chunk.size);
+            assert_eq!(hash(&stored), chunk_hash);
+        }
+        assert_eq!(store.get(&[0; 8]).unwrap(), None);
+        assert!(!store.contains(&[0; 8]).unwrap());
+    }
+
+    fn check_prune(store: &mut impl PrunableChunkStore<[u8; 8]>) {
+        let mut hashes = store.hashes().unwrap();
+        assert!(hashes.len() > 2);
+        let removed = hashes.split_off(hashes.len() / 2);
+        store.remove(&removed).unwrap();
+        store.remove(&[[0; 8]]).unwrap();
+
+        let mut remaining = store.hashes().unwrap();
+        remaining.sort_unstable();
+        hashes.sort_unstable();
+        assert_eq!(remaining, hashes);
+        assert!(!store.contains(&removed[0]).unwrap());
+    }
+
+    #[test]
+    fn memory_store() {
+        let mut store = MemoryChunkStore::new();
+        check_store(&mut store);
+        check_prune(&mut store);
+    }
+
+    #[test]
+    fn dir_store() {
+        let root = std::env::temp_dir().join(format!("cdc-dir-store-{}", std::process::id()));
+        let mut store = DirChunkStore::new(&root).unwrap();
+        check_store(&mut store);
+        check_prune(&mut store);
+        assert_eq!(
+            store.chunk_path(&[0xab, 0xcd]),
+            root.join("ab").join("abcd")
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn concurrent_puts() {
+        let root = std::env::temp_dir().join(format!("cdc-dir-puts-{}", std::process::id()));
+        let data = pseudo_random(100_000);
+        let chunk_hash = hash(&data);
+
+        std::thread::scope(|scope| {
+            let (data, chunk_hash) = (&data, &chunk_hash);
+            for _ in 0..8 {
+                let mut store = DirChunkStore::new(&root).unwrap();
+                _ = scope.spawn(move || store.put(chunk_hash, data).unwrap());
+            }
+        });
+        let store = DirChunkStore::new(&root).unwrap();
+        assert_eq!(store.get(&chunk_hash).unwrap(), Some(data));
+        let dir = store.chunk_path(&chunk_hash);
+        let files = std::fs::read_dir(dir.parent().unwrap()).unwrap();
+        assert_eq!(files.count(), 1);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,531 @@
+//! Compact, versioned binary encoding of [`Node`]s and trees of nodes.
+//!
+//! # Format
+//!
+//! All integers written as `varint` use the unsigned LEB128 encoding. All hashes
+//! of an encoded node or tree must have the same length, which is written once in
+//! the header.
+//!
+//! A single node is encoded as:
+//!
+//! ```text
+//! u8       format version (currently 2)
+//! u8       hash length `n`
+//! varint   level
+//! [u8; n]  hash
+//! varint   number of children
+//! ...      for each child:
+//!            u8       kind (0 for a chunk, 1 for a node)
+//!            [u8; n]  hash
+//!            varint   size
+//!            varint   number of chunks below the child, only for a node
+//! ```
+//!
+//! The size and number of chunks of a node are not encoded, they are
+//! calculated from its children when decoding.
+//!
+//! A tree (a sequence of nodes, as emitted by [`NodeIter`](crate::NodeIter)) is encoded as:
+//!
+//! ```text
+//! [u8; 4]  magic "CDCT"
+//! u8       format version (currently 2)
+//! u8       hash length `n`
+//! varint   number of nodes
+//! ...      for each node: level, hash, number of children and children,
+//!          laid out as in the single node encoding
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{Child, ChildKind, Node};
+
+/// The magic bytes at the start of an encoded tree.
+pub const TREE_MAGIC: [u8; 4] = *b"CDCT";
+
+/// The current version of the binary format of nodes and trees, bumped on any change of the format.
+pub const FORMAT_VERSION: u8 = 2;
+
+/// An error which can occur when decoding binary data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum DecodeError {
+    /// The data ended before the decoding was complete.
+    UnexpectedEof,
+
+    /// The data doesn't start with the expected magic bytes.
+    InvalidMagic,
+
+    /// The format version is not supported by this version of the crate.
+    UnsupportedVersion(u8),
+
+    /// A varint is longer than allowed for a `u64`.
+    VarintOverflow,
+
+    /// A length doesn't fit the remaining data.
+    InvalidLength(u64),
+
+    /// A hash couldn't be converted into the hash type.
+    InvalidHash,
+
+    /// A node has no children.
+    EmptyNode,
+
+    /// The kind of a child is unknown.
+    InvalidChildKind(u8),
+
+    /// A size or a number of chunks overflows a `u64`.
+    SizeOverflow,
+
+    /// There is data left after the decoding was complete.
+    TrailingBytes(usize),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEof => write!(f, "unexpected end of data"),
+            Self::InvalidMagic => write!(f, "invalid magic bytes"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {version}")
+            }
+            Self::VarintOverflow => write!(f, "varint overflows a u64"),
+            Self::InvalidLength(len) => write!(f, "invalid length {len}"),
+            Self::InvalidHash => write!(f, "invalid hash"),
+            Self::EmptyNode => write!(f, "node without children"),
+            Self::InvalidChildKind(kind) => write!(f, "invalid child kind {kind}"),
+            Self::SizeOverflow => write!(f, "size overflows a u64"),
+            Self::TrailingBytes(nb_bytes) => write!(f, "{nb_bytes} trailing bytes"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+/// Appends `value` to `buf` as an unsigned LEB128 varint.
+// Only the 7 lowest bits of `value` are kept on purpose.
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Decodes an unsigned LEB128 varint, its bytes being read one at a time.
+///
+/// Returns `None` if the varint overflows a `u64`.
+fn decode_varint<E>(mut next_byte: impl FnMut() -> Result<u8, E>) -> Result<Option<u64>, E> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = next_byte()?;
+        let bits = u64::from(byte & 0x7f);
+        if (bits << shift) >> shift != bits {
+            return Ok(None);
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(feature = "std")]
+/// Reads an unsigned LEB128 varint from a reader.
+///
+/// # Errors
+///
+/// Returns an [`std::io::ErrorKind::InvalidData`] error wrapping a [`DecodeError`] if the varint
+/// overflows a `u64`, or the error of the reader.
+pub(crate) fn read_varint(reader: &mut impl std::io::Read) -> std::io::Result<u64> {
+    let next_byte = || -> std::io::Result<u8> {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        Ok(byte[0])
+    };
+    decode_varint(next_byte)?.ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, DecodeError::VarintOverflow)
+    })
+}
+
+/// Returns the length of the hashes, checking that all of them have the same length.
+///
+/// # Panics
+///
+/// Panics if the hashes don't all have the same length, or if it is longer than 255 bytes.
+pub(crate) fn common_hash_len<'a, H: AsRef<[u8]> + 'a>(
+    hashes: impl IntoIterator<Item = &'a H>,
+) -> u8 {
+    let mut hash_len = None;
+    for hash in hashes {
+        let len = hash.as_ref().len();
+        assert_eq!(
+            *hash_len.get_or_insert(len),
+            len,
+            "all hashes must have the same length"
+        );
+    }
+    u8::try_from(hash_len.unwrap_or(0)).expect("hash length must be at most 255 bytes")
+}
+
+/// A cursor reading binary data.
+#[derive(Debug)]
+pub(crate) struct Decoder<'a> {
+    /// The data to decode.
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    /// Creates a new `Decoder`.
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    /// Reads `len` bytes.
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if len > self.bytes.len() {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    /// Reads a single byte.
+    pub(crate) fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /// Reads an unsigned LEB128 varint.
+    pub(crate) fn varint(&mut self) -> Result<u64, DecodeError> {
+        decode_varint(|| self.u8())?.ok_or(DecodeError::VarintOverflow)
+    }
+
+    /// Reads a varint used as the number of items, each at least `item_size` bytes long.
+    ///
+    /// The count is checked against the remaining data, so that corrupted data
+    /// cannot trigger huge allocations.
+    pub(crate) fn count(&mut self, item_size: usize) -> Result<usize, DecodeError> {
+        let count = self.varint()?;
+        match usize::try_from(count) {
+            Ok(n) if n.saturating_mul(item_size) <= self.bytes.len() => Ok(n),
+            _ => Err(DecodeError::InvalidLength(count)),
+        }
+    }
+
+    /// Reads a hash of `len` bytes.
+    pub(crate) fn hash<H>(&mut self, len: usize) -> Result<H, DecodeError>
+    where
+        H: for<'b> TryFrom<&'b [u8]>,
+    {
+        H::try_from(self.bytes(len)?).map_err(|_| DecodeError::InvalidHash)
+    }
+
+    /// Checks that all the data was consumed.
+    pub(crate) fn finish(self) -> Result<(), DecodeError> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(DecodeError::TrailingBytes(self.bytes.len()))
+        }
+    }
+}
+
+impl<H: AsRef<[u8]>> Node<H> {
+    /// Encodes the node in the binary format described in the [`codec`](crate::codec) module.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the hashes of the node don't all have the same length,
+    /// or if it is longer than 255 bytes.
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let hash_len = common_hash_len(self.hashes());
+        let mut buf =
+            Vec::with_capacity(8 + (self.children.len() + 1) * (4 + usize::from(hash_len)));
+        buf.push(FORMAT_VERSION);
+        buf.push(hash_len);
+        self.encode_body(&mut buf);
+        buf
+    }
+
+    /// Returns the hash of the node followed by the hashes of its children.
+    fn hashes(&self) -> impl Iterator<Item = &H> {
+        core::iter::once(&self.hash).chain(self.children.iter().map(|child| &child.hash))
+    }
+
+    /// Appends the level, hash and children of the node to `buf`.
+    fn encode_body(&self, buf: &mut Vec<u8>) {
+        write_varint(buf, self.level as u64);
+        buf.extend_from_slice(self.hash.as_ref());
+        write_varint(buf, self.children.len() as u64);
+        for child in &self.children {
+            match child.kind {
+                ChildKind::Leaf => {
+                    buf.push(0);
+                    buf.extend_from_slice(child.hash.as_ref());
+                    write_varint(buf, child.size);
+                }
+                ChildKind::Node => {
+                    buf.push(1);
+                    buf.extend_from_slice(child.hash.as_ref());
+                    write_varint(buf, child.size);
+                    write_varint(buf, child.nb_chunks);
+                }
+            }
+        }
+    }
+}
+
+impl<H> Node<H>
+where
+    H: for<'a> TryFrom<&'a [u8]>,
+{
+    /// Decodes a node encoded with [`Node::encode`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`DecodeError`] if the data is not a valid encoded node.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut decoder = Decoder::new(bytes);
+        let hash_len = decode_header(&mut decoder)?;
+        let node = Self::decode_body(&mut decoder, hash_len)?;
+        decoder.finish()?;
+        Ok(node)
+    }
+
+    /// Reads the level, hash and children of a node.
+    fn decode_body(decoder: &mut Decoder<'_>, hash_len: usize) -> Result<Self, DecodeError> {
+        let level = decoder.varint()?;
+        let level = usize::try_from(level).map_err(|_| DecodeError::InvalidLength(level))?;
+        let hash = decoder.hash(hash_len)?;
+        // A child is at least made of a kind, a hash and a size.
+        let nb_children = decoder.count(2 + hash_len)?;
+        if nb_children == 0 {
+            return Err(DecodeError::EmptyNode);
+        }
+        let children: Vec<Child<H>> = (0..nb_children)
+            .map(|_| match decoder.u8()? {
+                0 => Ok(Child::leaf(decoder.hash(hash_len)?, decoder.varint()?)),
+                1 => Ok(Child::node(
+                    decoder.hash(hash_len)?,
+                    decoder.varint()?,
+                    decoder.varint()?,
+                )),
+                kind => Err(DecodeError::InvalidChildKind(kind)),
+            })
+            .collect::<Result<_, _>>()?;
+
+        let (mut size, mut nb_chunks) = (0u64, 0u64);
+        for child in &children {
+            size = size
+                .checked_add(child.size)
+                .ok_or(DecodeError::SizeOverflow)?;
+            nb_chunks = nb_chunks
+                .checked_add(child.nb_chunks)
+                .ok_or(DecodeError::SizeOverflow)?;
+        }
+
+        Ok(Self {
+            hash,
+            level,
+            size,
+            nb_chunks,
+            children,
+        })
+    }
+}
+
+/// Reads the format version and the hash length.
+fn decode_header(decoder: &mut Decoder<'_>) -> Result<usize, DecodeError> {
+    let version = decoder.u8()?;
+    if version != FORMAT_VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    Ok(usize::from(decoder.u8()?))
+}
+
+/// Encodes a sequence of nodes in the binary format described in the [`codec`](crate::codec) module.
+///
+/// # Arguments
+///
+/// * `nodes` - The nodes to encode, usually in the order they were emitted by [`NodeIter`](crate::NodeIter).
+///
+/// # Panics
+///
+/// Panics if the hashes of the nodes don't all have the same length,
+/// or if it is longer than 255 bytes.
+#[must_use]
+pub fn encode_tree<H: AsRef<[u8]>>(nodes: &[Node<H>]) -> Vec<u8> {
+    let hash_len = common_hash_len(nodes.iter().flat_map(Node::hashes));
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&TREE_MAGIC);
+    buf.push(FORMAT_VERSION);
+    buf.push(hash_len);
+    write_varint(&mut buf, nodes.len() as u64);
+    for node in nodes {
+        node.encode_body(&mut buf);
+    }
+    buf
+}
+
+/// Decodes a sequence of nodes encoded with [`encode_tree`].
+///
+/// # Errors
+///
+/// Returns a [`DecodeError`] if the data is not a valid encoded tree.
+pub fn decode_tree<H>(bytes: &[u8]) -> Result<Vec<Node<H>>, DecodeError>
+where
+    H: for<'a> TryFrom<&'a [u8]>,
+{
+    let mut decoder = Decoder::new(bytes);
+    if decoder.bytes(TREE_MAGIC.len())? != TREE_MAGIC {
+        return Err(DecodeError::InvalidMagic);
+    }
+    let hash_len = decode_header(&mut decoder)?;
+    // A node is at least made of a level, a hash, a count and a child.
+    let nb_nodes = decoder.count(5 + 2 * hash_len)?;
+    let nodes = (0..nb_nodes)
+        .map(|_| Node::decode_body(&mut decoder, hash_len))
+        .collect::<Result<_, _>>()?;
+    decoder.finish()?;
+
+    Ok(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codec::*;
+
+    type Hash = [u8; 4];
+
+    fn node(hash: u8, level: usize, children: &[u8]) -> Node<Hash> {
+        let children = children
+            .iter()
+            .map(|c| {
+                if *c < 4 {
+                    Child::leaf([*c; 4], u64::from(*c) * 100)
+                } else {
+                    Child::node([*c; 4], 1000, 3)
+                }
+            })
+            .collect();
+        Node::new([hash; 4], level, children)
+    }
+
+    #[test]
+    fn varint() {
+        for value in [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut buf = vec![];
+            write_varint(&mut buf, value);
+            let mut decoder = Decoder::new(&buf);
+            assert_eq!(decoder.varint(), Ok(value));
+            assert_eq!(decoder.finish(), Ok(()));
+            #[cfg(feature = "std")]
+            assert_eq!(read_varint(&mut &buf[..]).unwrap(), value);
+        }
+
+        let overflow = [0xff; 10];
+        assert_eq!(
+            Decoder::new(&overflow).varint(),
+            Err(DecodeError::VarintOverflow)
+        );
+        #[cfg(feature = "std")]
+        {
+            let err = read_varint(&mut &overflow[..]).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+            let err = read_varint(&mut &[0x80][..]).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        }
+    }
+
+    #[test]
+    fn node_round_trip() {
+        let original = node(9, 2, &[1, 2, 4]);
+        let bytes = original.encode();
+        assert_eq!(
+            bytes.len(),
+            2 + 1 + 4 + 1 + (1 + 4 + 1) + (1 + 4 + 2) + (1 + 4 + 2 + 1)
+        );
+
+        assert_eq!(Node::<Hash>::decode(&bytes), Ok(original));
+    }
+
+    #[test]
+    fn tree_round_trip() {
+        let nodes = vec![node(4, 0, &[1, 2]), node(5, 1, &[4, 3])];
+        assert_eq!(decode_tree::<Hash>(&encode_tree(&nodes)), Ok(nodes));
+    }
+
+    #[test]
+    fn invalid_data() {
+        let bytes = node(9, 2, &[1, 2, 3]).encode();
+
+        assert_eq!(
+            Node::<Hash>::decode(&bytes[..5]).unwrap_err(),
+            DecodeError::UnexpectedEof
+        );
+        assert_eq!(
+            Node::<[u8; 8]>::decode(&bytes).unwrap_err(),
+            DecodeError::InvalidHash
+        );
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Node::<Hash>::decode(&trailing).unwrap_err(),
+            DecodeError::TrailingBytes(1)
+        );
+
+        let mut kind = bytes.clone();
+        kind[8] = 2;
+        assert_eq!(
+            Node::<Hash>::decode(&kind).unwrap_err(),
+            DecodeError::InvalidChildKind(2)
+        );
+
+        let mut overflow = node(9, 2, &[4]);
+        overflow.children.push(Child::node([5; 4], 1, u64::MAX));
+        assert_eq!(
+            Node::<Hash>::decode(&overflow.encode()).unwrap_err(),
+            DecodeError::SizeOverflow
+        );
+
+        let mut version = bytes;
+        version[0] = 42;
+        assert_eq!(
+            Node::<Hash>::decode(&version).unwrap_err(),
+            DecodeError::UnsupportedVersion(42)
+        );
+
+        assert_eq!(
+            decode_tree::<Hash>(b"CDCX\x02\x04\x00").unwrap_err(),
+            DecodeError::InvalidMagic
+        );
+        // The first version, without the kinds and sizes of the children.
+        assert_eq!(
+            decode_tree::<Hash>(b"CDCT\x01\x04\x00").unwrap_err(),
+            DecodeError::UnsupportedVersion(1)
+        );
+        assert_eq!(
+            decode_tree::<Hash>(b"CDCT\x02\x04\xff\x01").unwrap_err(),
+            DecodeError::InvalidLength(255)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let node = node(10, 2, &[1, 5, 2]);
+        let json = serde_json::to_string(&node).unwrap();
+        assert_eq!(serde_json::from_str::<Node<Hash>>(&json).unwrap(), node);
+
+        let err = DecodeError::UnsupportedVersion(3);
+        let json = serde_json::to_string(&err).unwrap();
+        assert_eq!(serde_json::from_str::<DecodeError>(&json).unwrap(), err);
+    }
+}
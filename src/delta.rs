@@ -0,0 +1,319 @@
+//! Deltas between two versions of a file, describing the new version with the chunks of the old one.
+//!
+//! Both versions are chunked with the same parameters: the chunks of the new version which are
+//! also in the old version are copied from it, the other ones are inserted as literal bytes.
+//!
+//! # Format
+//!
+//! A delta is encoded with the same conventions as the [`codec`](crate::codec) module,
+//! all integers written as `varint` using the unsigned LEB128 encoding:
+//!
+//! ```text
+//! [u8; 4]  magic "CDCD"
+//! u8       format version (currently 1)
+//! varint   number of operations
+//! ...      for each operation:
+//!            u8       kind (0 for a copy, 1 for an insert)
+//!            varint   offset in the old version, only for a copy
+//!            varint   length
+//!            [u8]     literal bytes, only for an insert
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::{self, Formatter};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+
+use crate::codec::{write_varint, Decoder};
+use crate::{ChunkIter, ChunkerParams, DecodeError};
+
+/// The magic bytes at the start of an encoded delta.
+pub const DELTA_MAGIC: [u8; 4] = *b"CDCD";
+
+/// The current version of the delta format, bumped on any change of the format.
+pub const DELTA_VERSION: u8 = 1;
+
+/// An error which can occur when decoding a delta.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum DeltaError {
+    /// The data is not a valid encoded delta.
+    Decode(DecodeError),
+
+    /// The kind of an operation is unknown.
+    InvalidOperation(u8),
+}
+
+impl fmt::Display for DeltaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decode(err) => write!(f, "invalid encoded delta: {err}"),
+            Self::InvalidOperation(kind) => write!(f, "invalid delta operation {kind}"),
+        }
+    }
+}
+
+impl std::error::Error for DeltaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Decode(err) => Some(err),
+            Self::InvalidOperation(_) => None,
+        }
+    }
+}
+
+impl From<DecodeError> for DeltaError {
+    fn from(err: DecodeError) -> Self {
+        Self::Decode(err)
+    }
+}
+
+/// An operation of a delta, producing a part of the new version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeltaOp {
+    /// Copies bytes from the old version.
+    Copy {
+        /// The offset of the bytes in the old version.
+        offset: u64,
+
+        /// The number of bytes.
+        len: u64,
+    },
+
+    /// Inserts literal bytes.
+    Insert(Vec<u8>),
+}
+
+/// A delta between two versions of a file, to reconstruct the new version from the old one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Delta {
+    /// The operations producing the new version, in order.
+    pub ops: Vec<DeltaOp>,
+}
+
+impl Delta {
+    /// Computes the delta between two versions of a file.
+    ///
+    /// Consecutive copies of contiguous bytes and consecutive inserts are merged.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - The parameters used to chunk both versions.
+    /// * `old` - The old version.
+    /// * `new` - The new version.
+    #[must_use]
+    pub fn compute(params: &ChunkerParams, old: &[u8], new: &[u8]) -> Self {
+        let mut old_chunks = HashMap::new();
+        for range in chunk_ranges(params, old) {
+            #[allow(clippy::cast_possible_truncation)] // The ranges are in `old`.
+            let data = &old[range.start as usize..range.end as usize];
+            _ = old_chunks.entry(data).or_insert(range.start);
+        }
+
+        let mut delta = Self::default();
+        for range in chunk_ranges(params, new) {
+            #[allow(clippy::cast_possible_truncation)] // The ranges are in `new`.
+            let data = &new[range.start as usize..range.end as usize];
+            match old_chunks.get(data) {
+                Some(offset) => delta.copy(*offset, data.len() as u64),
+                None => delta.insert(data),
+            }
+        }
+
+        delta
+    }
+
+    /// Appends a copy, merging it with the previous one if they are contiguous.
+    fn copy(&mut self, offset: u64, len: u64) {
+        if let Some(DeltaOp::Copy {
+            offset: last_offset,
+            len: last_len,
+        }) = self.ops.last_mut()
+        {
+            if *last_offset + *last_len == offset {
+                *last_len += len;
+                return;
+            }
+        }
+        self.ops.push(DeltaOp::Copy { offset, len });
+    }
+
+    /// Appends an insert, merging it with the previous one.
+    fn insert(&mut self, data: &[u8]) {
+        if let Some(DeltaOp::Insert(last)) = self.ops.last_mut() {
+            last.extend_from_slice(data);
+        } else {
+            self.ops.push(DeltaOp::Insert(data.to_vec()));
+        }
+    }
+
+    /// Returns the size of the new version.
+    #[must_use]
+    pub fn new_size(&self) -> u64 {
+        self.ops
+            .iter()
+            .map(|op| match op {
+                DeltaOp::Copy { len, .. } => *len,
+                DeltaOp::Insert(data) => data.len() as u64,
+            })
+            .sum()
+    }
+
+    /// Returns the number of literal bytes, which are not copied from the old version.
+    #[must_use]
+    pub fn literal_size(&self) -> u64 {
+        self.ops
+            .iter()
+            .map(|op| match op {
+                DeltaOp::Copy { .. } => 0,
+                DeltaOp::Insert(data) => data.len() as u64,
+            })
+            .sum()
+    }
+
+    /// Reconstructs the new version from the old one.
+    ///
+    /// # Arguments
+    ///
+    /// * `old` - The reader of the old version.
+    /// * `new` - The writer of the new version.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading or writing fails, or an [`io::ErrorKind::UnexpectedEof`]
+    /// error if a copy is out of the old version.
+    pub fn apply<R, W>(&self, mut old: R, mut new: W) -> io::Result<()>
+    where
+        R: Read + Seek,
+        W: Write,
+    {
+        for op in &self.ops {
+            match op {
+                DeltaOp::Copy { offset, len } => {
+                    _ = old.seek(SeekFrom::Start(*offset))?;
+                    if io::copy(&mut (&mut old).take(*len), &mut new)? != *len {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                }
+                DeltaOp::Insert(data) => new.write_all(data)?,
+            }
+        }
+        new.flush()
+    }
+
+    /// Encodes the delta in the binary format described in the [`delta`](crate::delta) module.
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&DELTA_MAGIC);
+        buf.push(DELTA_VERSION);
+        write_varint(&mut buf, self.ops.len() as u64);
+        for op in &self.ops {
+            match op {
+                DeltaOp::Copy { offset, len } => {
+                    buf.push(0);
+                    write_varint(&mut buf, *offset);
+                    write_varint(&mut buf, *len);
+                }
+                DeltaOp::Insert(data) => {
+                    buf.push(1);
+                    write_varint(&mut buf, data.len() as u64);
+                    buf.extend_from_slice(data);
+                }
+            }
+        }
+        buf
+    }
+
+    /// Decodes a delta encoded with [`Delta::encode`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`DeltaError`] if the data is not a valid encoded delta.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DeltaError> {
+        let mut decoder = Decoder::new(bytes);
+        if decoder.bytes(DELTA_MAGIC.len())? != DELTA_MAGIC {
+            return Err(DecodeError::InvalidMagic.into());
+        }
+        let version = decoder.u8()?;
+        if version != DELTA_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version).into());
+        }
+
+        // An operation is at least made of a kind and a length.
+        let nb_ops = decoder.count(2)?;
+        let mut ops = Vec::with_capacity(nb_ops);
+        for _ in 0..nb_ops {
+            let op = match decoder.u8()? {
+                0 => DeltaOp::Copy {
+                    offset: decoder.varint()?,
+                    len: decoder.varint()?,
+                },
+                1 => {
+                    let len = decoder.count(1)?;
+                    DeltaOp::Insert(decoder.bytes(len)?.to_vec())
+                }
+                kind => return Err(DeltaError::InvalidOperation(kind)),
+            };
+            ops.push(op);
+        }
+        decoder.finish()?;
+
+        Ok(Self { ops })
+    }
+}
+
+/// Returns the ranges of the chunks of some data.
+fn chunk_ranges<'a>(
+    params: &ChunkerParams,
+    data: &'a [u8],
+) -> impl Iterator<Item = Range<u64>> + 'a {
+    let separators = params.separators(data.iter().copied());
+    ChunkIter::new(separators, data.len() as u64).map(|chunk| chunk.index - chunk.size..chunk.index)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::delta::*;
+    use crate::test_utils::pseudo_random;
+
+    #[test]
+    fn delta_round_trip() {
+        let params = ChunkerParams {
+            separator_size_nb_bits: 4,
+            boundary_nb_bits: 8,
+            ..ChunkerParams::default()
+        };
+        let old = pseudo_random(40_000);
+        let mut new = old.clone();
+        new[10_000..10_010].copy_from_slice(b"0123456789");
+        _ = new.splice(25_000..25_000, b"inserted bytes".iter().copied());
+        _ = new.drain(35_000..36_000);
+
+        let delta = Delta::compute(&params, &old, &new);
+        assert_eq!(delta.new_size(), new.len() as u64);
+        assert!(delta.literal_size() < 4_000);
+
+        let delta = Delta::decode(&delta.encode()).unwrap();
+        let mut restored = vec![];
+        delta.apply(Cursor::new(&old), &mut restored).unwrap();
+        assert_eq!(restored, new);
+
+        let err = delta.apply(Cursor::new(&old[..20_000]), &mut vec![]);
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut bytes = delta.encode();
+        bytes[6] = 2;
+        assert_eq!(Delta::decode(&bytes), Err(DeltaError::InvalidOperation(2)));
+        bytes[0] = b'X';
+        assert_eq!(
+            Delta::decode(&bytes),
+            Err(DeltaError::Decode(DecodeError::InvalidMagic))
+        );
+    }
+}
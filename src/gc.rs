@@ -0,0 +1,237 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::io;
+
+use crate::{Child, ChildKind, Manifest, MissingNodeError, NodeStore, PrunableChunkStore};
+
+/// The set of chunks referenced by live manifests and trees.
+#[derive(Debug, Clone)]
+pub struct LiveChunks<H> {
+    /// The hashes of the referenced chunks.
+    chunks: HashSet<H>,
+
+    /// The hashes of the nodes already walked.
+    nodes: HashSet<H>,
+}
+
+impl<H> Default for LiveChunks<H> {
+    fn default() -> Self {
+        Self {
+            chunks: HashSet::new(),
+            nodes: HashSet::new(),
+        }
+    }
+}
+
+impl<H: Eq + Hash + Clone> LiveChunks<H> {
+    /// Creates a new, empty `LiveChunks`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of referenced chunks.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Returns `true` if no chunk is referenced.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Returns `true` if the chunk with the given hash is referenced.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the chunk.
+    pub fn contains(&self, hash: &H) -> bool {
+        self.chunks.contains(hash)
+    }
+
+    /// Marks the chunks of a manifest as referenced.
+    ///
+    /// # Arguments
+    ///
+    /// * `manifest` - The live manifest.
+    pub fn add_manifest(&mut self, manifest: &Manifest<H>) {
+        self.chunks
+            .extend(manifest.entries.iter().map(|entry| entry.hash.clone()));
+    }
+
+    /// Marks the chunks of a tree as referenced, walking the nodes from its root.
+    ///
+    /// The subtrees shared with the trees already added are not walked again.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The store containing the nodes of the tree.
+    /// * `root` - The root of the live tree.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MissingNodeError`] if a node of the tree is not in the store, as the chunks
+    /// below it can't be known: collecting garbage would then remove live chunks. Nothing is
+    /// marked then, and the tree can be added again once the store is repaired.
+    pub fn add_tree<S>(&mut self, store: &S, root: Child<H>) -> Result<(), MissingNodeError<H>>
+    where
+        S: NodeStore<H>,
+    {
+        // Only merged on success, so that a walked node always has all its chunks marked.
+        let mut chunks = HashSet::new();
+        let mut nodes = HashSet::new();
+        let mut stack = vec![root];
+        while let Some(child) = stack.pop() {
+            match child.kind {
+                ChildKind::Leaf => _ = chunks.insert(child.hash),
+                ChildKind::Node => {
+                    if self.nodes.contains(&child.hash) || nodes.contains(&child.hash) {
+                        continue;
+                    }
+                    let Some(node) = store.get(&child.hash) else {
+                        return Err(MissingNodeError { hash: child.hash });
+                    };
+                    _ = nodes.insert(child.hash);
+                    stack.extend(node.children);
+                }
+            }
+        }
+
+        self.chunks.extend(chunks);
+        self.nodes.extend(nodes);
+        Ok(())
+    }
+}
+
+/// The result of a garbage collection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GcReport<H> {
+    /// The number of chunks of the store which are referenced.
+    pub live_chunks: u64,
+
+    /// The hashes of the chunks of the store which are not referenced.
+    pub unreferenced: Vec<H>,
+
+    /// `true` if the unreferenced chunks were removed from the store, `false` for a dry run.
+    pub removed: bool,
+}
+
+/// Finds the chunks of a store which are not referenced, and removes them unless it is a dry run.
+///
+/// # Arguments
+///
+/// * `store` - The store to collect the garbage of.
+/// * `live` - The chunks referenced by the live manifests and trees.
+/// * `dry_run` - If `true`, only reports the unreferenced chunks without removing them.
+///
+/// # Errors
+///
+/// Returns an error if the store can't be accessed.
+pub fn collect_garbage<S, H>(
+    store: &mut S,
+    live: &LiveChunks<H>,
+    dry_run: bool,
+) -> io::Result<GcReport<H>>
+where
+    S: PrunableChunkStore<H>,
+    H: Eq + Hash + Clone,
+{
+    let (referenced, unreferenced): (Vec<H>, Vec<H>) = store
+        .hashes()?
+        .into_iter()
+        .partition(|hash| live.contains(hash));
+    if !dry_run && !unreferenced.is_empty() {
+        store.remove(&unreferenced)?;
+    }
+
+    Ok(GcReport {
+        live_chunks: referenced.len() as u64,
+        unreferenced,
+        removed: !dry_run,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn collect_unreferenced_chunks() {
+        let mut store = MemoryChunkStore::new();
+        for hash in 0..6u64 {
+            _ = store.put(&hash, &hash.to_le_bytes()).unwrap();
+        }
+
+        let mut nodes = MemoryNodeStore::new();
+        let subtree = Node::new(10, 1, vec![Child::leaf(0, 8), Child::leaf(1, 8)]);
+        nodes.put(Node::new(
+            11,
+            2,
+            vec![subtree.as_child(), Child::leaf(2, 8)],
+        ));
+        nodes.put(subtree);
+        let manifest = Manifest::from_chunks(
+            ChunkerParams::default(),
+            [3, 0].map(|hash| {
+                let chunk = Chunk {
+                    index: 8,
+                    size: 8,
+                    separator_hash: 0,
+                };
+                (chunk, hash)
+            }),
+        );
+
+        let mut live = LiveChunks::new();
+        live.add_tree(&nodes, Child::node(11, 24, 3)).unwrap();
+        live.add_manifest(&manifest);
+        assert_eq!(live.len(), 4);
+        assert_eq!(
+            live.add_tree(&nodes, Child::node(12, 8, 1)),
+            Err(MissingNodeError { hash: 12 })
+        );
+
+        let mut report = collect_garbage(&mut store, &live, true).unwrap();
+        report.unreferenced.sort_unstable();
+        assert_eq!(
+            report,
+            GcReport {
+                live_chunks: 4,
+                unreferenced: vec![4, 5],
+                removed: false,
+            }
+        );
+        assert_eq!(store.len(), 6);
+
+        let report = collect_garbage(&mut store, &live, false).unwrap();
+        assert!(report.removed);
+        assert_eq!(store.len(), 4);
+        assert!(!store.contains(&5).unwrap());
+    }
+
+    #[test]
+    fn add_tree_again_after_missing_node() {
+        let mut nodes = MemoryNodeStore::new();
+        let present = Node::new(16, 1, vec![Child::leaf(4, 8), Child::leaf(5, 8)]);
+        let missing = Node::new(17, 1, vec![Child::leaf(6, 8), Child::leaf(7, 8)]);
+        let root = Node::new(15, 2, vec![present.as_child(), missing.as_child()]);
+        let root_child = root.as_child();
+        nodes.put(root);
+        nodes.put(present);
+
+        let mut live = LiveChunks::new();
+        assert_eq!(
+            live.add_tree(&nodes, root_child),
+            Err(MissingNodeError { hash: 17 })
+        );
+        assert!(live.is_empty());
+
+        nodes.put(missing);
+        live.add_tree(&nodes, root_child).unwrap();
+        assert_eq!(live.len(), 4);
+        assert!((4..8).all(|hash| live.contains(&hash)));
+    }
+}
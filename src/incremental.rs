@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+
+use crate::predicate::ByRef;
+use crate::{BoundaryPredicate, Child, Chunk, Node, SeparatorIter};
+
+/// A local edit of a stream: `old_len` bytes at `offset` were replaced by `new_len` bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Edit {
+    /// The offset of the edit in the stream.
+    pub offset: u64,
+
+    /// The number of bytes removed from the old stream.
+    pub old_len: u64,
+
+    /// The number of bytes inserted in the new stream.
+    pub new_len: u64,
+}
+
+impl Edit {
+    /// Converts an offset of the old stream located after the edit into an offset of the new stream.
+    fn shift(&self, old_offset: u64) -> u64 {
+        old_offset - self.old_len + self.new_len
+    }
+}
+
+/// The result of the re-chunking of an edited stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChunkUpdate {
+    /// All the chunks of the new stream.
+    pub chunks: Vec<Chunk>,
+
+    /// The range of the old chunks which were replaced.
+    pub removed: Range<usize>,
+
+    /// The range of the new chunks which replaced them.
+    ///
+    /// # Note
+    ///
+    /// Only these chunks need to be hashed again, the other hashed chunks can be
+    /// updated with `hashed_chunks.splice(update.removed, new_hashed_chunks)`.
+    pub added: Range<usize>,
+}
+
+/// A chunker updating the chunks of a stream after a local edit, without re-chunking the whole stream.
+///
+/// As the rolling hash is reset after each separator, chunking from any previous chunk boundary
+/// gives the same separators as chunking from the start of the stream. The stream is re-chunked
+/// from the last boundary before the edit, until a separator found after the edit matches a
+/// boundary of the old chunks.
+#[derive(Debug, Clone, Copy)]
+pub struct IncrementalChunker<F> {
+    /// The number of bits of the separator size.
+    separator_size_nb_bits: u32,
+
+    /// The predicate used to determine if a separator is a separator boundary.
+    predicate: F,
+}
+
+impl IncrementalChunker<fn(u64) -> bool> {
+    /// Creates a new `IncrementalChunker`, finding the same separators as [`SeparatorIter::new`].
+    #[must_use]
+    pub fn new() -> Self {
+        #[inline]
+        fn default_predicate(x: u64) -> bool {
+            const BITMASK: u64 = (1u64 << 13) - 1;
+            x & BITMASK == BITMASK
+        }
+
+        Self::custom_new(6, default_predicate)
+    }
+}
+
+impl Default for IncrementalChunker<fn(u64) -> bool> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F> IncrementalChunker<F>
+where
+    F: BoundaryPredicate,
+{
+    /// Creates a new `IncrementalChunker`, finding the same separators as [`SeparatorIter::custom_new`].
+    ///
+    /// # Arguments
+    ///
+    /// * `separator_size_nb_bits` - The number of bits of the separator size.
+    /// * `predicate` - The predicate used to determine if a separator is a separator boundary.
+    pub fn custom_new(separator_size_nb_bits: u32, predicate: F) -> Self {
+        Self {
+            separator_size_nb_bits,
+            predicate,
+        }
+    }
+
+    /// Updates the chunks of a stream after an edit.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_chunks` - The chunks of the stream before the edit.
+    /// * `edit` - The edit applied to the stream.
+    /// * `reader` - A reader of the stream after the edit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the edit is outside of the old stream, or if reading the new stream fails.
+    pub fn update<R: Read + Seek>(
+        &self,
+        old_chunks: &[Chunk],
+        edit: &Edit,
+        mut reader: R,
+    ) -> io::Result<ChunkUpdate> {
+        let old_length = old_chunks.last().map_or(0, |chunk| chunk.index);
+        let edit_end = edit.offset.checked_add(edit.old_len);
+        if edit_end.map_or(true, |edit_end| edit_end > old_length) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "edit outside of the stream",
+            ));
+        }
+        let new_length = edit.shift(old_length);
+        let edit_end = edit.offset + edit.new_len;
+
+        // Restarts from the last boundary before the edit, `chunk.index` being the end of the chunk.
+        // The end of the last chunk is the end of the stream, not a boundary: the last chunk is
+        // always re-chunked, even if the edit is an append.
+        let first_removed = old_chunks
+            .partition_point(|chunk| chunk.index <= edit.offset)
+            .min(old_chunks.len().saturating_sub(1));
+        let restart = first_removed
+            .checked_sub(1)
+            .map_or(0, |index| old_chunks[index].index);
+        _ = reader.seek(SeekFrom::Start(restart))?;
+
+        let mut new_chunks = vec![];
+        let mut last_removed = old_chunks.len();
+        let mut last_index = restart;
+        let mut error = None;
+        {
+            let byte_iter = BufReader::new(reader)
+                .take(new_length - restart)
+                .bytes()
+                .map_while(|byte| byte.map_err(|err| error = Some(err)).ok());
+            let separators = SeparatorIter::custom_new(
+                byte_iter,
+                self.separator_size_nb_bits,
+                ByRef(&self.predicate),
+            );
+            for separator in separators {
+                let index = restart + separator.index;
+                new_chunks.push(Chunk {
+                    index,
+                    size: index - last_index,
+                    separator_hash: separator.hash,
+                });
+                last_index = index;
+
+                // The data after a separator found after the edit is unchanged,
+                // chunks are the same from there if it was already a boundary.
+                if index >= edit_end {
+                    let old_index = index - edit.new_len + edit.old_len;
+                    if let Ok(position) =
+                        old_chunks.binary_search_by_key(&old_index, |chunk| chunk.index)
+                    {
+                        last_removed = position + 1;
+                        break;
+                    }
+                }
+            }
+        }
+        if let Some(err) = error {
+            return Err(err);
+        }
+        if last_removed == old_chunks.len() && last_index < new_length {
+            new_chunks.push(Chunk {
+                index: new_length,
+                size: new_length - last_index,
+                separator_hash: 0, // any value is ok, last chunk of the stream.
+            });
+        }
+
+        let added = first_removed..first_removed + new_chunks.len();
+        let mut chunks = Vec::with_capacity(old_chunks.len() - last_removed + added.end);
+        chunks.extend_from_slice(&old_chunks[..first_removed]);
+        chunks.extend(new_chunks);
+        chunks.extend(old_chunks[last_removed..].iter().map(|chunk| Chunk {
+            index: edit.shift(chunk.index),
+            ..*chunk
+        }));
+
+        Ok(ChunkUpdate {
+            chunks,
+            removed: first_removed..last_removed,
+            added,
+        })
+    }
+}
+
+/// Wraps a function creating nodes, so that the nodes of a previous tree are reused
+/// instead of being created again.
+///
+/// [`NodeIter`](crate::NodeIter) still has to run on all the updated hashed chunks, grouping
+/// them into nodes, but with the returned function only the nodes affected by an edit are
+/// created (and hashed) by `new_node`: the other ones are found by their children, and cloned
+/// from the previous tree.
+///
+/// # Arguments
+///
+/// * `old_nodes` - The nodes of the previous tree.
+/// * `new_node` - The function to create a new node.
+pub fn reuse_nodes<H, F>(
+    old_nodes: impl IntoIterator<Item = Node<H>>,
+    new_node: F,
+) -> impl Fn(usize, &Vec<Child<H>>) -> Node<H>
+where
+    H: Eq + Hash + Clone,
+    F: Fn(usize, &Vec<Child<H>>) -> Node<H>,
+{
+    let old_nodes: HashMap<Vec<Child<H>>, Node<H>> = old_nodes
+        .into_iter()
+        .map(|node| (node.children.clone(), node))
+        .collect();
+
+    move |level, children| match old_nodes.get(children.as_slice()) {
+        Some(node) if node.level == level => node.clone(),
+        _ => new_node(level, children),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::io::{self, Cursor};
+
+    use crate::test_utils::{new_node, random_data, tree, tree_chunks};
+    use crate::*;
+
+    fn predicate(x: u64) -> bool {
+        x & 0xff == 0xff
+    }
+
+    fn chunks(data: &[u8]) -> Vec<Chunk> {
+        let separators = SeparatorIter::custom_new(data.iter().copied(), 4, predicate);
+        ChunkIter::new(separators, data.len() as u64).collect()
+    }
+
+    #[test]
+    fn update_matches_full_chunking() {
+        let old_data = random_data(100_000, 42);
+        let old_chunks = chunks(&old_data);
+        let chunker = IncrementalChunker::custom_new(4, predicate);
+        let last_chunk_start = usize::try_from(old_chunks[old_chunks.len() - 2].index).unwrap();
+
+        for (offset, old_len, inserted) in [
+            (50_000, 10, random_data(300, 7)),
+            (0, 0, random_data(20, 8)),
+            (99_990, 10, vec![]),
+            (12_345, 1_000, vec![]),
+            // Appends.
+            (100_000, 0, random_data(5_000, 9)),
+            (100_000, 0, random_data(3, 10)),
+            // Inside the last chunk.
+            (last_chunk_start + 3, 2, random_data(2_000, 11)),
+        ] {
+            let mut new_data = old_data.clone();
+            _ = new_data.splice(offset..offset + old_len, inserted.iter().copied());
+            let edit = Edit {
+                offset: offset as u64,
+                old_len: old_len as u64,
+                new_len: inserted.len() as u64,
+            };
+
+            let update = chunker
+                .update(&old_chunks, &edit, Cursor::new(&new_data))
+                .unwrap();
+            assert_eq!(update.chunks, chunks(&new_data));
+            assert!(update.removed.len() < 10);
+            assert_eq!(
+                update.chunks.len() - update.added.len(),
+                old_chunks.len() - update.removed.len()
+            );
+        }
+
+        for (offset, old_len) in [(99_995, 10), (10, u64::MAX)] {
+            let edit = Edit {
+                offset,
+                old_len,
+                new_len: 0,
+            };
+            let err = chunker
+                .update(&old_chunks, &edit, Cursor::new(&old_data))
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn reused_nodes() {
+        let mut hashed_chunks = tree_chunks(|_| 1);
+        let (old_nodes, _) = tree(&hashed_chunks);
+        let nb_old_nodes = old_nodes.len();
+
+        hashed_chunks[11].hash = 100;
+        let nb_created = Cell::new(0);
+        let counted_new_node = |level, children: &Vec<Child<u64>>| {
+            nb_created.set(nb_created.get() + 1);
+            new_node(level, children)
+        };
+        let mut node_iter = NodeIter::new(
+            hashed_chunks.iter().copied(),
+            reuse_nodes(old_nodes, counted_new_node),
+            0,
+        );
+        let new_nodes: Vec<_> = node_iter.by_ref().collect();
+        assert_eq!(new_nodes.len(), nb_old_nodes);
+        assert!(nb_created.get() < nb_old_nodes);
+
+        assert_eq!(tree(&hashed_chunks), (new_nodes, node_iter.root().unwrap()));
+    }
+}
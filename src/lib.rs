@@ -1,13 +1,91 @@
 //! This crate provides a set of tools to work with Content Defined Chunking (CDC) algorithms.
+//!
+//! # Features
+//!
+//! - `std` (default): the helpers based on readers, writers, files and hash maps: chunking a
+//!   reader, chunk stores, packs, restoring, deltas, synchronization, garbage collection and
+//!   scrubbing. Without it, the crate is `no_std` and only needs `alloc`.
+//! - `serde`: implements `Serialize` and `Deserialize` for the public value types.
+//! - `cli`: the `cdc` command-line binary.
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+mod ae;
 mod chunk;
+#[cfg(feature = "std")]
+mod chunk_data;
+#[cfg(feature = "std")]
+mod chunk_store;
+pub mod codec;
+#[cfg(feature = "std")]
+pub mod delta;
+#[cfg(feature = "std")]
+mod gc;
+#[cfg(feature = "std")]
+mod incremental;
+pub mod manifest;
+mod multi;
+mod node_store;
+#[cfg(feature = "std")]
+pub mod pack;
+mod params;
 mod polynom;
+mod predicate;
+mod quick;
+mod ram;
+mod range;
+#[cfg(feature = "std")]
+mod restore;
 mod rolling_hash;
+#[cfg(feature = "std")]
+mod scrub;
 mod separator;
+mod stats;
+#[cfg(feature = "std")]
+pub mod sync;
+#[cfg(test)]
+pub(crate) mod test_utils;
 mod tree;
+mod tttd;
 
+pub use ae::AeSeparatorIter;
 pub use chunk::{Chunk, ChunkIter};
+#[cfg(feature = "std")]
+pub use chunk_data::ChunkDataIter;
+#[cfg(feature = "std")]
+pub use chunk_store::{
+    store_chunks, ChunkStore, DirChunkStore, MemoryChunkStore, PrunableChunkStore, StoreStats,
+};
+pub use codec::{decode_tree, encode_tree, DecodeError};
+#[cfg(feature = "std")]
+pub use delta::{Delta, DeltaError, DeltaOp};
+#[cfg(feature = "std")]
+pub use gc::{collect_garbage, GcReport, LiveChunks};
+#[cfg(feature = "std")]
+pub use incremental::{reuse_nodes, ChunkUpdate, Edit, IncrementalChunker};
+pub use manifest::{Manifest, ManifestEntry, ManifestError};
+pub use multi::{MultiChunkIter, MultiSeparator, MultiSeparatorIter};
+#[cfg(feature = "std")]
+pub use node_store::MemoryNodeStore;
+pub use node_store::{LeafIter, MissingNodeError, NodeStore, TreeError};
+#[cfg(feature = "std")]
+pub use pack::{PackEntry, PackReader, PackStore, PackWriter};
+pub use params::ChunkerParams;
 pub use polynom::{Polynom, Polynom64};
+pub use predicate::{BoundaryPredicate, Mask, NormalizedMasks, Threshold};
+pub use quick::{KnownChunks, QuickSeparatorIter};
+pub use ram::RamSeparatorIter;
+pub use range::{find_range, ChunkSlice};
+#[cfg(feature = "std")]
+pub use restore::{ChunkReader, RestoreError};
 pub use rolling_hash::{Rabin64, RollingHash64};
+#[cfg(feature = "std")]
+pub use scrub::{CorruptedChunk, Scrub, ScrubReport};
 pub use separator::{HashToLevel, Separator, SeparatorIter};
-pub use tree::{HashedChunk, Node, NodeIter};
+pub use stats::{AsChunk, ChunkStats, ChunkStatsSummary, StatsIter};
+#[cfg(feature = "std")]
+pub use sync::{SyncError, SyncStats};
+pub use tree::{Child, ChildKind, HashedChunk, Node, NodeIter};
+pub use tttd::TttdSeparatorIter;
@@ -0,0 +1,464 @@
+//! A manifest records how a file was chunked.
+//!
+//! # Format
+//!
+//! A manifest is encoded with the same conventions as the [`codec`](crate::codec) module,
+//! all integers written as `varint` using the unsigned LEB128 encoding:
+//!
+//! ```text
+//! [u8; 4]  magic "CDCM"
+//! u8       format version (currently 1)
+//! u8       hash length `n`
+//! varint   separator size number of bits
+//! varint   boundary number of bits
+//! varint   level 0 number of bits
+//! varint   level up number of bits
+//! varint   maximum number of children of a node
+//! u8       root kind (0 for no root, 1 for a chunk, 2 for a node)
+//! [u8; n]  root hash, if there is a root
+//! varint   root size, if there is a root
+//! varint   root number of chunks, if the root is a node
+//! varint   number of chunks
+//! ...      for each chunk:
+//!            [u8; n]  hash
+//!            varint   size
+//!            varint   level
+//! ```
+//!
+//! The offsets of the chunks are not encoded, they are calculated from their sizes.
+//!
+//! When decoding or deserializing, the manifest is validated against its recorded parameters:
+//! they must be valid, all the chunks except the last one must be at least as large as the
+//! window size, the levels must be reachable, and the root must cover all the chunks. The
+//! offsets of the deserialized chunks are calculated from their sizes too.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::codec::{common_hash_len, write_varint, Decoder};
+use crate::{Child, ChildKind, Chunk, ChunkerParams, DecodeError, HashedChunk};
+
+/// The magic bytes at the start of an encoded manifest.
+pub const MANIFEST_MAGIC: [u8; 4] = *b"CDCM";
+
+/// The current version of the manifest format, bumped on any change of the format.
+pub const MANIFEST_VERSION: u8 = 1;
+
+/// An error which can occur when decoding a manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ManifestError {
+    /// The data is not a valid encoded manifest.
+    Decode(DecodeError),
+
+    /// The chunker parameters are not valid.
+    InvalidParameters,
+
+    /// A chunk is not consistent with the chunker parameters.
+    InvalidChunk(u64),
+
+    /// The root doesn't match the chunks.
+    InvalidRoot,
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decode(err) => write!(f, "invalid encoded manifest: {err}"),
+            Self::InvalidParameters => write!(f, "invalid chunker parameters"),
+            Self::InvalidChunk(index) => write!(f, "invalid chunk {index}"),
+            Self::InvalidRoot => write!(f, "root doesn't match the chunks"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ManifestError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Decode(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for ManifestError {
+    fn from(err: DecodeError) -> Self {
+        Self::Decode(err)
+    }
+}
+
+/// A chunk of a file recorded in a manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ManifestEntry<H> {
+    /// The hash of the chunk.
+    pub hash: H,
+
+    /// The offset of the chunk in the file.
+    pub offset: u64,
+
+    /// The size of the chunk.
+    pub size: u64,
+
+    /// The level of the chunk.
+    pub level: usize,
+}
+
+/// A record of how a file was chunked: its chunks, the parameters used and the root of its tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(
+        try_from = "RawManifest<H>",
+        bound(deserialize = "H: serde::Deserialize<'de> + PartialEq")
+    )
+)]
+pub struct Manifest<H> {
+    /// The parameters used to chunk the file.
+    pub params: ChunkerParams,
+
+    /// The chunks of the file, in order.
+    pub entries: Vec<ManifestEntry<H>>,
+
+    /// The root of the tree built from the chunks, if any.
+    pub root: Option<Child<H>>,
+}
+
+/// A deserialized manifest, before its offsets are calculated and it is validated.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawManifest<H> {
+    /// The parameters used to chunk the file.
+    params: ChunkerParams,
+
+    /// The chunks of the file, in order, with offsets which are not trusted.
+    entries: Vec<ManifestEntry<H>>,
+
+    /// The root of the tree built from the chunks, if any.
+    root: Option<Child<H>>,
+}
+
+#[cfg(feature = "serde")]
+impl<H: PartialEq> TryFrom<RawManifest<H>> for Manifest<H> {
+    type Error = ManifestError;
+
+    fn try_from(raw: RawManifest<H>) -> Result<Self, Self::Error> {
+        let mut manifest = Self {
+            params: raw.params,
+            entries: raw.entries,
+            root: raw.root,
+        };
+        let mut offset = 0u64;
+        for entry in &mut manifest.entries {
+            entry.offset = offset;
+            offset = offset
+                .checked_add(entry.size)
+                .ok_or(DecodeError::SizeOverflow)?;
+        }
+        manifest.validate()?;
+        Ok(manifest)
+    }
+}
+
+impl<H> Manifest<H> {
+    /// Creates a new `Manifest` from chunks and their hash, without root.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - The parameters used to chunk the file.
+    /// * `chunks` - The chunks of the file, in order, with their hash.
+    pub fn from_chunks(
+        params: ChunkerParams,
+        chunks: impl IntoIterator<Item = (Chunk, H)>,
+    ) -> Self {
+        let hash_to_level = params.hash_to_level();
+        let entries = chunks
+            .into_iter()
+            .map(|(chunk, hash)| ManifestEntry {
+                hash,
+                offset: chunk.index - chunk.size,
+                size: chunk.size,
+                level: hash_to_level.to_level(chunk.separator_hash),
+            })
+            .collect();
+
+        Self {
+            params,
+            entries,
+            root: None,
+        }
+    }
+
+    /// Returns the size of the file.
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        self.entries
+            .last()
+            .map_or(0, |entry| entry.offset + entry.size)
+    }
+
+    /// Returns the chunks as hashed chunks, to build a tree with [`NodeIter`](crate::NodeIter).
+    pub fn hashed_chunks(&self) -> impl Iterator<Item = HashedChunk<H>> + '_
+    where
+        H: Copy,
+    {
+        self.entries.iter().map(|entry| HashedChunk {
+            hash: entry.hash,
+            level: entry.level,
+            size: entry.size,
+        })
+    }
+}
+
+impl<H: AsRef<[u8]>> Manifest<H> {
+    /// Encodes the manifest in the binary format described in the [`manifest`](crate::manifest) module.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the hashes of the manifest don't all have the same length,
+    /// or if it is longer than 255 bytes.
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let hashes = self.entries.iter().map(|entry| &entry.hash);
+        let hash_len = common_hash_len(hashes.chain(self.root.as_ref().map(|root| &root.hash)));
+        let mut buf = Vec::with_capacity(32 + self.entries.len() * (4 + usize::from(hash_len)));
+        buf.extend_from_slice(&MANIFEST_MAGIC);
+        buf.push(MANIFEST_VERSION);
+        buf.push(hash_len);
+
+        write_varint(&mut buf, u64::from(self.params.separator_size_nb_bits));
+        write_varint(&mut buf, u64::from(self.params.boundary_nb_bits));
+        write_varint(&mut buf, u64::from(self.params.lvl0_nb_bits));
+        write_varint(&mut buf, u64::from(self.params.lvlup_nb_bits));
+        write_varint(&mut buf, self.params.max_node_children as u64);
+
+        match &self.root {
+            None => buf.push(0),
+            Some(root) => {
+                buf.push(match root.kind {
+                    ChildKind::Leaf => 1,
+                    ChildKind::Node => 2,
+                });
+                buf.extend_from_slice(root.hash.as_ref());
+                write_varint(&mut buf, root.size);
+                if root.kind == ChildKind::Node {
+                    write_varint(&mut buf, root.nb_chunks);
+                }
+            }
+        }
+
+        write_varint(&mut buf, self.entries.len() as u64);
+        for entry in &self.entries {
+            buf.extend_from_slice(entry.hash.as_ref());
+            write_varint(&mut buf, entry.size);
+            write_varint(&mut buf, entry.level as u64);
+        }
+
+        buf
+    }
+}
+
+impl<H> Manifest<H>
+where
+    H: for<'a> TryFrom<&'a [u8]> + PartialEq,
+{
+    /// Decodes a manifest encoded with [`Manifest::encode`], and validates it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ManifestError`] if the data is not a valid encoded manifest, or if the
+    /// manifest is not valid.
+    pub fn decode(bytes: &[u8]) -> Result<Self, ManifestError> {
+        let mut decoder = Decoder::new(bytes);
+        if decoder.bytes(MANIFEST_MAGIC.len())? != MANIFEST_MAGIC {
+            return Err(DecodeError::InvalidMagic.into());
+        }
+        let version = decoder.u8()?;
+        if version != MANIFEST_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version).into());
+        }
+        let hash_len = usize::from(decoder.u8()?);
+
+        let mut param = || {
+            let value = decoder.varint()?;
+            u32::try_from(value).map_err(|_| ManifestError::InvalidParameters)
+        };
+        let params = ChunkerParams {
+            separator_size_nb_bits: param()?,
+            boundary_nb_bits: param()?,
+            lvl0_nb_bits: param()?,
+            lvlup_nb_bits: param()?,
+            max_node_children: param()? as usize,
+        };
+
+        let root = match decoder.u8()? {
+            0 => None,
+            1 => Some(Child::leaf(decoder.hash(hash_len)?, decoder.varint()?)),
+            2 => Some(Child::node(
+                decoder.hash(hash_len)?,
+                decoder.varint()?,
+                decoder.varint()?,
+            )),
+            kind => return Err(DecodeError::InvalidChildKind(kind).into()),
+        };
+
+        // A chunk is at least made of a hash, a size and a level.
+        let nb_entries = decoder.count(2 + hash_len)?;
+        let mut entries = Vec::with_capacity(nb_entries);
+        let mut offset = 0u64;
+        for _ in 0..nb_entries {
+            let hash = decoder.hash(hash_len)?;
+            let size = decoder.varint()?;
+            let level = decoder.varint()?;
+            let level = usize::try_from(level).map_err(|_| DecodeError::InvalidLength(level))?;
+            entries.push(ManifestEntry {
+                hash,
+                offset,
+                size,
+                level,
+            });
+            offset = offset.checked_add(size).ok_or(DecodeError::SizeOverflow)?;
+        }
+        decoder.finish()?;
+
+        let manifest = Self {
+            params,
+            entries,
+            root,
+        };
+        manifest.validate()?;
+        Ok(manifest)
+    }
+}
+
+impl<H: PartialEq> Manifest<H> {
+    /// Validates the parameters, and the chunks and the root against them.
+    fn validate(&self) -> Result<(), ManifestError> {
+        if !self.params.is_valid() {
+            return Err(ManifestError::InvalidParameters);
+        }
+
+        let max_level = self.params.max_level();
+        let last = self.entries.len().saturating_sub(1);
+        for (index, entry) in self.entries.iter().enumerate() {
+            let too_small =
+                entry.size == 0 || (index < last && entry.size < self.params.window_size());
+            if too_small || entry.level > max_level {
+                return Err(ManifestError::InvalidChunk(index as u64));
+            }
+        }
+
+        let valid_root = match &self.root {
+            None => true,
+            Some(root) if root.size != self.size() => false,
+            Some(root) => match root.kind {
+                ChildKind::Leaf => self.entries.len() == 1 && root.hash == self.entries[0].hash,
+                ChildKind::Node => root.nb_chunks == self.entries.len() as u64,
+            },
+        };
+        if !valid_root {
+            return Err(ManifestError::InvalidRoot);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::manifest::*;
+    use crate::test_utils::periodic_data;
+
+    /// Returns the manifest of periodic data, with 2-byte hashes and a root node.
+    fn manifest() -> Manifest<[u8; 2]> {
+        let params = ChunkerParams {
+            separator_size_nb_bits: 4,
+            boundary_nb_bits: 8,
+            lvl0_nb_bits: 8,
+            lvlup_nb_bits: 2,
+            max_node_children: 0,
+        };
+        let data = periodic_data(30_000);
+        let chunks = params.chunk_data(&data[..]).map(|chunk| {
+            let (chunk, chunk_data) = chunk.unwrap();
+            let hash = [chunk_data[0], chunk_data[chunk_data.len() - 1]];
+            (chunk, hash)
+        });
+        let mut manifest = Manifest::from_chunks(params, chunks);
+        assert_eq!(manifest.size(), data.len() as u64);
+        manifest.root = Some(Child::node(
+            [1, 2],
+            data.len() as u64,
+            manifest.entries.len() as u64,
+        ));
+        manifest
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut manifest = manifest();
+        let bytes = manifest.encode();
+        assert_eq!(Manifest::decode(&bytes), Ok(manifest.clone()));
+        assert_eq!(
+            Manifest::<[u8; 2]>::decode(&bytes[..bytes.len() - 1]),
+            Err(ManifestError::Decode(DecodeError::UnexpectedEof))
+        );
+
+        manifest.entries[0].size = 1;
+        manifest.entries[1].offset -= 15;
+        manifest.root.as_mut().unwrap().size -= 15;
+        assert_eq!(
+            Manifest::<[u8; 2]>::decode(&manifest.encode()),
+            Err(ManifestError::InvalidChunk(0))
+        );
+
+        manifest.root = Some(Child::leaf([1, 2], 10));
+        manifest.entries.truncate(1);
+        assert_eq!(
+            Manifest::<[u8; 2]>::decode(&manifest.encode()),
+            Err(ManifestError::InvalidRoot)
+        );
+
+        manifest.params.separator_size_nb_bits = 0;
+        assert_eq!(
+            Manifest::<[u8; 2]>::decode(&manifest.encode()),
+            Err(ManifestError::InvalidParameters)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let manifest = manifest();
+        assert!(manifest.entries.len() > 10);
+        let json = serde_json::to_value(&manifest).unwrap();
+        assert_eq!(
+            serde_json::from_value::<Manifest<[u8; 2]>>(json.clone()).unwrap(),
+            manifest
+        );
+
+        // The offsets are calculated from the sizes.
+        let mut offsets = json.clone();
+        offsets["entries"][3]["offset"] = 0.into();
+        assert_eq!(
+            serde_json::from_value::<Manifest<[u8; 2]>>(offsets).unwrap(),
+            manifest
+        );
+
+        let mut invalid = json.clone();
+        invalid["entries"][0]["size"] = 1.into();
+        let err = serde_json::from_value::<Manifest<[u8; 2]>>(invalid).unwrap_err();
+        assert_eq!(err.to_string(), ManifestError::InvalidChunk(0).to_string());
+
+        let mut invalid = json;
+        invalid["params"]["separator_size_nb_bits"] = 0.into();
+        let err = serde_json::from_value::<Manifest<[u8; 2]>>(invalid).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            ManifestError::InvalidParameters.to_string()
+        );
+    }
+}
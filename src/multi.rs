@@ -0,0 +1,256 @@
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{BoundaryPredicate, Chunk, Separator, SeparatorIter, Threshold};
+
+/// A separator found by a [`MultiSeparatorIter`], which is a boundary for some of its target
+/// sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultiSeparator {
+    /// The index of the separator in the stream.
+    pub index: u64,
+
+    /// The hash of the separator.
+    pub hash: u64,
+
+    /// The number of resolutions the separator is a boundary for: it ends a chunk for the
+    /// `nb_resolutions` smallest target sizes.
+    pub nb_resolutions: usize,
+}
+
+impl MultiSeparator {
+    /// Returns `true` if the separator is a boundary for a resolution.
+    ///
+    /// # Arguments
+    ///
+    /// * `resolution` - The index of the target size.
+    #[must_use]
+    pub fn is_boundary(&self, resolution: usize) -> bool {
+        resolution < self.nb_resolutions
+    }
+
+    /// Returns the separator, without its resolutions.
+    #[must_use]
+    pub fn separator(&self) -> Separator {
+        Separator {
+            index: self.index,
+            hash: self.hash,
+        }
+    }
+}
+
+/// An iterator that separates data for several target sizes in a single pass of the rolling
+/// hash.
+///
+/// The separators are the ones of a [`SeparatorIter`] with a [`Threshold`] for the smallest
+/// target size. A separator is a boundary for a larger target size when its hash is also below
+/// the threshold of this size: the boundaries of a larger size are always boundaries of the
+/// smaller ones, so the chunks of a resolution are made of whole chunks of the finer ones.
+///
+/// The rolling hash being reset after each separator of the smallest size, the boundaries of
+/// the larger sizes are not the ones a `SeparatorIter` for these sizes would find, and their
+/// average chunk size is a bit larger than the target, by the window size relative to the
+/// smallest target size.
+#[derive(Debug)]
+pub struct MultiSeparatorIter<I> {
+    /// The separators of the smallest target size.
+    separators: SeparatorIter<I, Threshold>,
+
+    /// The predicates of the other target sizes, in increasing order.
+    thresholds: Vec<Threshold>,
+}
+
+impl<I> MultiSeparatorIter<I>
+where
+    I: Iterator<Item = u8>,
+{
+    /// Creates a new `MultiSeparatorIter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `iter` - The iterator to separate.
+    /// * `separator_size_nb_bits` - The number of bits of the separator size.
+    /// * `target_sizes` - The target sizes, in increasing order, see [`Threshold::new`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `target_sizes` is empty or not in strictly increasing order, or if a size is not
+    /// a valid [`Threshold`] target size.
+    pub fn new(iter: I, separator_size_nb_bits: u32, target_sizes: &[u64]) -> Self {
+        let [smallest, larger @ ..] = target_sizes else {
+            panic!("no target size");
+        };
+        assert!(
+            target_sizes.windows(2).all(|sizes| sizes[0] < sizes[1]),
+            "the target sizes must be in strictly increasing order"
+        );
+
+        Self {
+            separators: SeparatorIter::custom_new(
+                iter,
+                separator_size_nb_bits,
+                Threshold::new(*smallest),
+            ),
+            thresholds: larger.iter().map(|size| Threshold::new(*size)).collect(),
+        }
+    }
+
+    /// Returns the number of resolutions, i.e. of target sizes.
+    #[must_use]
+    pub fn nb_resolutions(&self) -> usize {
+        self.thresholds.len() + 1
+    }
+}
+
+impl<I> Iterator for MultiSeparatorIter<I>
+where
+    I: Iterator<Item = u8>,
+{
+    type Item = MultiSeparator;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let separator = self.separators.next()?;
+        let nb_larger = self
+            .thresholds
+            .iter()
+            .take_while(|threshold| threshold.is_boundary(separator.hash, 0))
+            .count();
+
+        Some(MultiSeparator {
+            index: separator.index,
+            hash: separator.hash,
+            nb_resolutions: nb_larger + 1,
+        })
+    }
+}
+
+/// An iterator that chunks data for several target sizes in a single pass, see
+/// [`MultiSeparatorIter`].
+///
+/// It yields the chunks of all the resolutions with the index of their target size, in the
+/// order of their ends, from the smallest target size to the largest for the same end.
+#[derive(Debug)]
+pub struct MultiChunkIter<I> {
+    /// The separators of all the resolutions.
+    separators: MultiSeparatorIter<I>,
+
+    /// The length of the stream.
+    stream_length: u64,
+
+    /// The index of the last separator of each resolution.
+    last_separator_indexes: Vec<u64>,
+
+    /// The chunks found and not yielded yet.
+    pending: VecDeque<(usize, Chunk)>,
+
+    /// `true` if the separators are exhausted.
+    ended: bool,
+}
+
+impl<I> MultiChunkIter<I>
+where
+    I: Iterator<Item = u8>,
+{
+    /// Creates a new `MultiChunkIter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `separators` - The separators of all the resolutions.
+    /// * `stream_length` - The length of the stream.
+    pub fn new(separators: MultiSeparatorIter<I>, stream_length: u64) -> Self {
+        Self {
+            last_separator_indexes: vec![0; separators.nb_resolutions()],
+            separators,
+            stream_length,
+            pending: VecDeque::new(),
+            ended: false,
+        }
+    }
+
+    /// Ends the chunks of the first resolutions.
+    fn end_chunks(&mut self, nb_resolutions: usize, index: u64, separator_hash: u64) {
+        for (resolution, last_index) in self.last_separator_indexes[..nb_resolutions]
+            .iter_mut()
+            .enumerate()
+        {
+            if index > *last_index {
+                let chunk = Chunk {
+                    index,
+                    size: index - *last_index,
+                    separator_hash,
+                };
+                self.pending.push_back((resolution, chunk));
+                *last_index = index;
+            }
+        }
+    }
+}
+
+impl<I> Iterator for MultiChunkIter<I>
+where
+    I: Iterator<Item = u8>,
+{
+    type Item = (usize, Chunk);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() && !self.ended {
+            if let Some(separator) = self.separators.next() {
+                self.end_chunks(separator.nb_resolutions, separator.index, separator.hash);
+            } else {
+                self.ended = true;
+                // Any hash is ok, last chunks of the stream.
+                self.end_chunks(self.last_separator_indexes.len(), self.stream_length, 0);
+            }
+        }
+
+        self.pending.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{random_data, SEED};
+    use crate::*;
+
+    #[test]
+    fn multi_resolution() {
+        let data = random_data(400_000, SEED);
+        let target_sizes = [256, 1_000, 4_096];
+
+        let separators: Vec<MultiSeparator> =
+            MultiSeparatorIter::new(data.iter().copied(), 5, &target_sizes).collect();
+        let finest = SeparatorIter::custom_new(data.iter().copied(), 5, Threshold::new(256));
+        assert!(finest.eq(separators.iter().map(MultiSeparator::separator)));
+
+        let multi_chunks = MultiChunkIter::new(
+            MultiSeparatorIter::new(data.iter().copied(), 5, &target_sizes),
+            400_000,
+        );
+        let mut chunks = vec![vec![]; 3];
+        for (resolution, chunk) in multi_chunks {
+            chunks[resolution].push(chunk);
+        }
+
+        for (resolution, target_size) in target_sizes.into_iter().enumerate() {
+            let boundaries = separators
+                .iter()
+                .filter(|separator| separator.is_boundary(resolution))
+                .map(MultiSeparator::separator);
+            let expected: Vec<Chunk> = ChunkIter::new(boundaries, 400_000).collect();
+            assert_eq!(chunks[resolution], expected);
+
+            let average = 400_000 / chunks[resolution].len() as u64;
+            assert!(average > target_size * 9 / 10 && average < target_size * 3 / 2);
+
+            // The boundaries of a resolution are boundaries of the finer ones.
+            if let Some(finer) = resolution.checked_sub(1) {
+                assert!(chunks[resolution]
+                    .iter()
+                    .all(|chunk| chunks[finer].iter().any(|finer| finer.index == chunk.index)));
+            }
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use alloc::{vec, vec::Vec};
+use core::fmt::{self, Debug};
+#[cfg(feature = "std")]
+use core::hash::Hash;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+use crate::{Child, ChildKind, Node};
+
+/// A storage for nodes, indexed by their hash.
+pub trait NodeStore<H> {
+    /// Returns the node with the given hash, if it is in the store.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the node.
+    fn get(&self, hash: &H) -> Option<Node<H>>;
+
+    /// Puts a node in the store.
+    ///
+    /// # Arguments
+    ///
+    /// * `node` - The node to put, indexed by its hash.
+    fn put(&mut self, node: Node<H>);
+}
+
+/// An error returned when a node referenced in a tree is not found in a `NodeStore`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MissingNodeError<H> {
+    /// The hash of the missing node.
+    pub hash: H,
+}
+
+impl<H: Debug> fmt::Display for MissingNodeError<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "node {:?} not found", self.hash)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<H: Debug> std::error::Error for MissingNodeError<H> {}
+
+/// An error returned when a tree can't be walked in a `NodeStore`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum TreeError<H> {
+    /// A node referenced in the tree is not in the store.
+    MissingNode(H),
+
+    /// A node is not at a lower level than its parent, as it would be in a tree built by
+    /// [`NodeIter`](crate::NodeIter): the tree may have a cycle.
+    InvalidLevel(H),
+}
+
+impl<H: Debug> fmt::Display for TreeError<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingNode(hash) => write!(f, "node {hash:?} not found"),
+            Self::InvalidLevel(hash) => write!(f, "node {hash:?} not below its parent"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<H: Debug> std::error::Error for TreeError<H> {}
+
+/// A `NodeStore` keeping the nodes in memory.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct MemoryNodeStore<H> {
+    /// The nodes, indexed by their hash.
+    nodes: HashMap<H, Node<H>>,
+}
+
+#[cfg(feature = "std")]
+impl<H> Default for MemoryNodeStore<H> {
+    fn default() -> Self {
+        Self {
+            nodes: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<H> MemoryNodeStore<H> {
+    /// Creates a new, empty `MemoryNodeStore`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of nodes in the store.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if the store contains no node.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<H: Eq + Hash + Clone> NodeStore<H> for MemoryNodeStore<H> {
+    fn get(&self, hash: &H) -> Option<Node<H>> {
+        self.nodes.get(hash).cloned()
+    }
+
+    fn put(&mut self, node: Node<H>) {
+        _ = self.nodes.insert(node.hash.clone(), node);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<H: Eq + Hash + Clone> Extend<Node<H>> for MemoryNodeStore<H> {
+    fn extend<T: IntoIterator<Item = Node<H>>>(&mut self, iter: T) {
+        for node in iter {
+            self.put(node);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<H: Eq + Hash + Clone> FromIterator<Node<H>> for MemoryNodeStore<H> {
+    fn from_iter<T: IntoIterator<Item = Node<H>>>(iter: T) -> Self {
+        let mut store = Self::new();
+        store.extend(iter);
+        store
+    }
+}
+
+/// An iterator that walks a tree top-down from its root, and yields the
+/// hashes of the chunks in stream order.
+///
+/// A node which is not found in the store is reported as a [`TreeError::MissingNode`],
+/// and the walk continues with its next sibling. The level of each node must be lower than
+/// the level of its parent, so that the walk ends even if the store contains a cycle: a node
+/// which is not is reported as a [`TreeError::InvalidLevel`], and its children are skipped.
+#[derive(Debug)]
+pub struct LeafIter<'a, S, H> {
+    /// The store containing the nodes of the tree.
+    store: &'a S,
+
+    /// The hashes and kinds of the children still to visit, with the level of their parent,
+    /// the next one being at the end.
+    stack: Vec<(H, ChildKind, usize)>,
+}
+
+impl<'a, S, H> LeafIter<'a, S, H>
+where
+    S: NodeStore<H>,
+{
+    /// Creates a new `LeafIter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The store containing the nodes of the tree.
+    /// * `root` - The root of the tree, as returned by [`NodeIter::root`](crate::NodeIter::root).
+    pub fn new(store: &'a S, root: Child<H>) -> Self {
+        Self {
+            store,
+            // The root has no parent, its level only has to be lower than the maximum.
+            stack: vec![(root.hash, root.kind, usize::MAX)],
+        }
+    }
+
+    /// Creates a new `LeafIter` from the hash of the root node of the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The store containing the nodes of the tree.
+    /// * `hash` - The hash of the root node, which must be in the store.
+    pub fn from_hash(store: &'a S, hash: H) -> Self {
+        Self {
+            store,
+            stack: vec![(hash, ChildKind::Node, usize::MAX)],
+        }
+    }
+}
+
+impl<S, H> Iterator for LeafIter<'_, S, H>
+where
+    S: NodeStore<H>,
+{
+    type Item = Result<H, TreeError<H>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((hash, kind, parent_level)) = self.stack.pop() {
+            match kind {
+                ChildKind::Leaf => return Some(Ok(hash)),
+                ChildKind::Node => match self.store.get(&hash) {
+                    Some(node) if node.level < parent_level => {
+                        let children = node.children.into_iter().rev();
+                        let level = node.level;
+                        self.stack
+                            .extend(children.map(|child| (child.hash, child.kind, level)));
+                    }
+                    Some(_) => return Some(Err(TreeError::InvalidLevel(hash))),
+                    None => return Some(Err(TreeError::MissingNode(hash))),
+                },
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::test_utils::{tree, tree_chunks, TREE_LEVELS};
+    use crate::*;
+
+    #[test]
+    fn leaves_in_stream_order() {
+        let (nodes, root) = tree(&tree_chunks(|index| index + 1));
+        assert_eq!(root, Child::node(nodes.last().unwrap().hash, 78, 12));
+        let mut store: MemoryNodeStore<u64> = nodes.into_iter().collect();
+
+        let leaves: Result<Vec<u64>, _> = LeafIter::new(&store, root).collect();
+        assert_eq!(leaves, Ok((0..TREE_LEVELS.len() as u64).collect()));
+
+        store = MemoryNodeStore::new();
+        let mut leaves = LeafIter::new(&store, root);
+        assert_eq!(leaves.next(), Some(Err(TreeError::MissingNode(root.hash))));
+        assert_eq!(leaves.next(), None);
+    }
+
+    #[test]
+    fn leaves_from_hash() {
+        let (nodes, root) = tree(&tree_chunks(|index| index + 1));
+        let store: MemoryNodeStore<u64> = nodes.into_iter().collect();
+
+        let leaves: Result<Vec<u64>, _> = LeafIter::from_hash(&store, root.hash).collect();
+        assert_eq!(leaves, Ok((0..TREE_LEVELS.len() as u64).collect()));
+
+        let mut leaves = LeafIter::from_hash(&store, 1_000);
+        assert_eq!(leaves.next(), Some(Err(TreeError::MissingNode(1_000))));
+        assert_eq!(leaves.next(), None);
+    }
+
+    #[test]
+    fn cycles() {
+        // A node referencing itself, and two nodes referencing each other.
+        let store: MemoryNodeStore<u64> = [
+            Node::new(1, 0, vec![Child::leaf(10, 1), Child::node(1, 1, 1)]),
+            Node::new(2, 1, vec![Child::node(3, 1, 1)]),
+            Node::new(3, 0, vec![Child::leaf(11, 1), Child::node(2, 1, 1)]),
+        ]
+        .into_iter()
+        .collect();
+
+        let leaves: Vec<_> = LeafIter::from_hash(&store, 1).collect();
+        assert_eq!(leaves, [Ok(10), Err(TreeError::InvalidLevel(1))]);
+        let leaves: Vec<_> = LeafIter::new(&store, Child::node(2, 2, 2)).collect();
+        assert_eq!(leaves, [Ok(11), Err(TreeError::InvalidLevel(2))]);
+    }
+
+    #[test]
+    fn single_chunk_root() {
+        let chunks = std::iter::once(HashedChunk {
+            hash: 7u64,
+            level: 3,
+            size: 10,
+        });
+        let new_node = |_, _: &Vec<Child<u64>>| unreachable!();
+        let mut node_iter = NodeIter::new(chunks, new_node, 0);
+        assert_eq!(node_iter.next(), None);
+        assert_eq!(node_iter.root(), Some(Child::leaf(7, 10)));
+
+        let store = MemoryNodeStore::new();
+        let leaves: Result<Vec<u64>, _> = LeafIter::new(&store, Child::leaf(7, 10)).collect();
+        assert_eq!(leaves, Ok(vec![7]));
+    }
+}
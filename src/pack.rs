@@ -0,0 +1,676 @@
+//! Pack files, grouping many chunks in a single file with a trailing index.
+//!
+//! # Format
+//!
+//! A pack file is encoded with the same conventions as the [`codec`](crate::codec) module,
+//! all integers written as `varint` using the unsigned LEB128 encoding:
+//!
+//! ```text
+//! ...      the data of the chunks, concatenated
+//! [u8; 4]  magic "CDCP"
+//! u8       format version (currently 1)
+//! u8       hash length `n`
+//! varint   number of chunks
+//! ...      for each chunk, in the order of the data:
+//!            [u8; n]  hash
+//!            varint   length
+//! u32      length of the index, from the magic bytes to here, in little endian
+//! ```
+//!
+//! The offsets of the chunks are not encoded, they are calculated from their lengths.
+//!
+//! A [`PackStore`] keeps its pack files in a directory with the layout of rustic: a pack is
+//! stored in `<root>/<xx>/<id>`, where `<id>` is the hash of the pack file in hexadecimal
+//! and `<xx>` the first byte of it.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Debug, Formatter};
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use crate::chunk_store::{to_hex, write_file};
+use crate::codec::{common_hash_len, write_varint, Decoder};
+use crate::{ChunkStore, DecodeError, PrunableChunkStore};
+
+/// The magic bytes at the start of the index of a pack file.
+pub const PACK_MAGIC: [u8; 4] = *b"CDCP";
+
+/// The current version of the pack file format, bumped on any change of the format.
+pub const PACK_VERSION: u8 = 1;
+
+/// The default maximum size of the data of a pack file.
+pub const DEFAULT_MAX_PACK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// The location of a chunk in a pack file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackEntry<H> {
+    /// The hash of the chunk.
+    pub hash: H,
+
+    /// The offset of the chunk in the pack file.
+    pub offset: u64,
+
+    /// The length of the chunk.
+    pub length: u64,
+}
+
+/// A writer of a pack file.
+#[derive(Debug)]
+pub struct PackWriter<W, H> {
+    /// The writer of the pack file.
+    writer: W,
+
+    /// The chunks written so far.
+    entries: Vec<PackEntry<H>>,
+
+    /// The size of the data written so far.
+    size: u64,
+}
+
+impl<W: Write, H: AsRef<[u8]>> PackWriter<W, H> {
+    /// Creates a new `PackWriter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer of the pack file.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            entries: Vec::new(),
+            size: 0,
+        }
+    }
+
+    /// Returns the size of the data written so far.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the chunks written so far.
+    pub fn entries(&self) -> &[PackEntry<H>] {
+        &self.entries
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Writes a chunk to the pack file.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the chunk.
+    /// * `data` - The data of the chunk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    pub fn add(&mut self, hash: H, data: &[u8]) -> io::Result<()> {
+        self.writer.write_all(data)?;
+        self.entries.push(PackEntry {
+            hash,
+            offset: self.size,
+            length: data.len() as u64,
+        });
+        self.size += data.len() as u64;
+        Ok(())
+    }
+
+    /// Writes the index, and returns the underlying writer with the chunks of the pack file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the hashes of the chunks don't all have the same length,
+    /// or if it is longer than 255 bytes.
+    pub fn finish(mut self) -> io::Result<(W, Vec<PackEntry<H>>)> {
+        let hash_len = common_hash_len(self.entries.iter().map(|entry| &entry.hash));
+        let mut index = Vec::with_capacity(16 + self.entries.len() * (4 + usize::from(hash_len)));
+        index.extend_from_slice(&PACK_MAGIC);
+        index.push(PACK_VERSION);
+        index.push(hash_len);
+        write_varint(&mut index, self.entries.len() as u64);
+        for entry in &self.entries {
+            index.extend_from_slice(entry.hash.as_ref());
+            write_varint(&mut index, entry.length);
+        }
+        let index_len = u32::try_from(index.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "pack index too large"))?;
+        index.extend_from_slice(&index_len.to_le_bytes());
+
+        self.writer.write_all(&index)?;
+        self.writer.flush()?;
+        Ok((self.writer, self.entries))
+    }
+}
+
+/// A reader of a pack file, looking chunks up by hash.
+#[derive(Debug)]
+pub struct PackReader<R, H> {
+    /// The reader of the pack file.
+    reader: R,
+
+    /// The chunks of the pack file.
+    entries: Vec<PackEntry<H>>,
+
+    /// The positions of the chunks in `entries`, indexed by their hash.
+    positions: HashMap<H, usize>,
+}
+
+impl<R, H> PackReader<R, H>
+where
+    R: Read + Seek,
+    H: for<'a> TryFrom<&'a [u8]> + Eq + Hash + Clone,
+{
+    /// Opens a pack file, reading its index.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader of the pack file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, or an [`io::ErrorKind::InvalidData`] error wrapping
+    /// a [`DecodeError`] if the index is not valid.
+    pub fn open(mut reader: R) -> io::Result<Self> {
+        let invalid = |err: DecodeError| io::Error::new(io::ErrorKind::InvalidData, err);
+
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        let mut index_len = [0; 4];
+        if file_len < 4 {
+            return Err(invalid(DecodeError::UnexpectedEof));
+        }
+        _ = reader.seek(SeekFrom::Start(file_len - 4))?;
+        reader.read_exact(&mut index_len)?;
+        let index_len = u64::from(u32::from_le_bytes(index_len));
+        let data_len = (file_len - 4)
+            .checked_sub(index_len)
+            .ok_or_else(|| invalid(DecodeError::InvalidLength(index_len)))?;
+
+        let mut index = vec![];
+        _ = reader.seek(SeekFrom::Start(data_len))?;
+        _ = (&mut reader).take(index_len).read_to_end(&mut index)?;
+        let entries = decode_index::<H>(&index, data_len).map_err(invalid)?;
+        let positions = entries
+            .iter()
+            .enumerate()
+            .map(|(position, entry)| (entry.hash.clone(), position))
+            .collect();
+
+        Ok(Self {
+            reader,
+            entries,
+            positions,
+        })
+    }
+
+    /// Returns the chunks of the pack file.
+    pub fn entries(&self) -> &[PackEntry<H>] {
+        &self.entries
+    }
+
+    /// Returns the location of a chunk in the pack file.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the chunk.
+    pub fn find(&self, hash: &H) -> Option<&PackEntry<H>> {
+        self.positions
+            .get(hash)
+            .map(|position| &self.entries[*position])
+    }
+
+    /// Returns the data of a chunk, if it is in the pack file.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the chunk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails.
+    pub fn get(&mut self, hash: &H) -> io::Result<Option<Vec<u8>>> {
+        match self.find(hash) {
+            Some(entry) => {
+                let entry = entry.clone();
+                read_chunk(&mut self.reader, &entry).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Decodes the index of a pack file.
+fn decode_index<H>(index: &[u8], data_len: u64) -> Result<Vec<PackEntry<H>>, DecodeError>
+where
+    H: for<'a> TryFrom<&'a [u8]>,
+{
+    let mut decoder = Decoder::new(index);
+    if decoder.bytes(PACK_MAGIC.len())? != PACK_MAGIC {
+        return Err(DecodeError::InvalidMagic);
+    }
+    let version = decoder.u8()?;
+    if version != PACK_VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    let hash_len = usize::from(decoder.u8()?);
+
+    // A chunk is at least made of a hash and a length.
+    let nb_entries = decoder.count(1 + hash_len)?;
+    let mut entries = Vec::with_capacity(nb_entries);
+    let mut offset = 0u64;
+    for _ in 0..nb_entries {
+        let hash = decoder.hash(hash_len)?;
+        let length = decoder.varint()?;
+        entries.push(PackEntry {
+            hash,
+            offset,
+            length,
+        });
+        offset = offset
+            .checked_add(length)
+            .ok_or(DecodeError::SizeOverflow)?;
+    }
+    decoder.finish()?;
+    if offset != data_len {
+        return Err(DecodeError::InvalidLength(offset));
+    }
+
+    Ok(entries)
+}
+
+/// Reads the data of a chunk from a pack file.
+fn read_chunk<R: Read + Seek, H>(reader: &mut R, entry: &PackEntry<H>) -> io::Result<Vec<u8>> {
+    _ = reader.seek(SeekFrom::Start(entry.offset))?;
+    let mut data = vec![];
+    _ = reader.take(entry.length).read_to_end(&mut data)?;
+    if data.len() as u64 != entry.length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(data)
+}
+
+/// A `ChunkStore` keeping the chunks in size-bounded pack files of a local directory.
+///
+/// The chunks are buffered in memory until their total size reaches the maximum pack size,
+/// then written to a new pack file. Dropping the store writes the buffered chunks, ignoring
+/// errors: call [`PackStore::flush`] before to handle them.
+pub struct PackStore<H, F>
+where
+    H: AsRef<[u8]> + Eq + Hash + Clone,
+    F: Fn(&[u8]) -> H,
+{
+    /// The root directory of the store.
+    root: PathBuf,
+
+    /// The function calculating the id of a pack file from its content.
+    hash: F,
+
+    /// The maximum size of the data of a pack file.
+    max_pack_size: u64,
+
+    /// The paths of the pack files.
+    packs: Vec<PathBuf>,
+
+    /// The locations of the chunks in the pack files, with the position of their pack file in `packs`.
+    index: HashMap<H, (usize, PackEntry<H>)>,
+
+    /// The pack file being written.
+    pending: PackWriter<Vec<u8>, H>,
+
+    /// The positions of the chunks in the pack file being written, indexed by their hash.
+    pending_index: HashMap<H, usize>,
+}
+
+impl<H, F> Debug for PackStore<H, F>
+where
+    H: AsRef<[u8]> + Eq + Hash + Clone,
+    F: Fn(&[u8]) -> H,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PackStore")
+            .field("root", &self.root)
+            .field("max_pack_size", &self.max_pack_size)
+            .field("packs", &self.packs)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<H, F> PackStore<H, F>
+where
+    H: for<'a> TryFrom<&'a [u8]> + AsRef<[u8]> + Eq + Hash + Clone,
+    F: Fn(&[u8]) -> H,
+{
+    /// Opens a `PackStore`, creating its root directory if needed and reading the
+    /// indexes of its pack files.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The root directory of the store.
+    /// * `max_pack_size` - The maximum size of the data of a pack file.
+    /// * `hash` - The function calculating the id of a pack file from its content.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory or a pack file can't be read.
+    pub fn open(root: impl Into<PathBuf>, max_pack_size: u64, hash: F) -> io::Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+
+        let mut store = Self {
+            root,
+            hash,
+            max_pack_size,
+            packs: Vec::new(),
+            index: HashMap::new(),
+            pending: PackWriter::new(Vec::new()),
+            pending_index: HashMap::new(),
+        };
+        for dir in fs::read_dir(&store.root)? {
+            let dir = dir?;
+            if !dir.file_type()?.is_dir() {
+                continue;
+            }
+            for pack in fs::read_dir(dir.path())? {
+                let path = pack?.path();
+                if path.extension().is_none() {
+                    let reader = PackReader::open(BufReader::new(File::open(&path)?))?;
+                    store.add_pack(path, reader.entries);
+                }
+            }
+        }
+
+        Ok(store)
+    }
+}
+
+impl<H, F> PackStore<H, F>
+where
+    H: AsRef<[u8]> + Eq + Hash + Clone,
+    F: Fn(&[u8]) -> H,
+{
+    /// Returns the paths of the pack files.
+    #[must_use]
+    pub fn packs(&self) -> &[PathBuf] {
+        &self.packs
+    }
+
+    /// Adds the chunks of a pack file to the index.
+    fn add_pack(&mut self, path: PathBuf, entries: Vec<PackEntry<H>>) {
+        let position = self.packs.len();
+        self.packs.push(path);
+        for entry in entries {
+            _ = self.index.insert(entry.hash.clone(), (position, entry));
+        }
+    }
+
+    /// Writes the buffered chunks to a new pack file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pack file can't be written.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.pending.entries().is_empty() {
+            return Ok(());
+        }
+        let pending = std::mem::replace(&mut self.pending, PackWriter::new(Vec::new()));
+        let (data, entries) = pending.finish()?;
+        self.pending_index.clear();
+
+        let id = to_hex((self.hash)(&data).as_ref());
+        let dir = self.root.join(id.get(..2).unwrap_or("00"));
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(id);
+
+        write_file(&path, &data)?;
+        self.add_pack(path, entries);
+        Ok(())
+    }
+}
+
+impl<H, F> Drop for PackStore<H, F>
+where
+    H: AsRef<[u8]> + Eq + Hash + Clone,
+    F: Fn(&[u8]) -> H,
+{
+    fn drop(&mut self) {
+        _ = self.flush();
+    }
+}
+
+impl<H, F> ChunkStore<H> for PackStore<H, F>
+where
+    H: for<'a> TryFrom<&'a [u8]> + AsRef<[u8]> + Eq + Hash + Clone,
+    F: Fn(&[u8]) -> H,
+{
+    fn contains(&self, hash: &H) -> io::Result<bool> {
+        Ok(self.index.contains_key(hash) || self.pending_index.contains_key(hash))
+    }
+
+    fn get(&self, hash: &H) -> io::Result<Option<Vec<u8>>> {
+        if let Some(position) = self.pending_index.get(hash) {
+            let entry = &self.pending.entries()[*position];
+            let mut reader = io::Cursor::new(self.pending.get_ref());
+            return read_chunk(&mut reader, entry).map(Some);
+        }
+        match self.index.get(hash) {
+            Some((pack, entry)) => {
+                let mut reader = File::open(&self.packs[*pack])?;
+                read_chunk(&mut reader, entry).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn put(&mut self, hash: &H, data: &[u8]) -> io::Result<bool> {
+        if self.contains(hash)? {
+            return Ok(false);
+        }
+        if !self.pending.entries().is_empty()
+            && self.pending.size() + data.len() as u64 > self.max_pack_size
+        {
+            self.flush()?;
+        }
+        _ = self
+            .pending_index
+            .insert(hash.clone(), self.pending.entries().len());
+        self.pending.add(hash.clone(), data)?;
+        Ok(true)
+    }
+}
+
+impl<H, F> PrunableChunkStore<H> for PackStore<H, F>
+where
+    H: for<'a> TryFrom<&'a [u8]> + AsRef<[u8]> + Eq + Hash + Clone,
+    F: Fn(&[u8]) -> H,
+{
+    fn hashes(&self) -> io::Result<Vec<H>> {
+        let packed = self.index.keys();
+        Ok(packed.chain(self.pending_index.keys()).cloned().collect())
+    }
+
+    /// Removes chunks from the store, rewriting the pack files containing them.
+    ///
+    /// The remaining chunks of these pack files are written to new pack files, in the order of
+    /// the old ones, before the old ones are deleted, and the buffered chunks are written too.
+    fn remove(&mut self, hashes: &[H]) -> io::Result<()> {
+        let removed: HashSet<&H> = hashes.iter().collect();
+
+        if hashes
+            .iter()
+            .any(|hash| self.pending_index.contains_key(hash))
+        {
+            let pending = std::mem::replace(&mut self.pending, PackWriter::new(Vec::new()));
+            self.pending_index.clear();
+            for entry in pending.entries() {
+                if !removed.contains(&entry.hash) {
+                    let data = read_chunk(&mut io::Cursor::new(pending.get_ref()), entry)?;
+                    _ = self.put(&entry.hash, &data)?;
+                }
+            }
+        }
+
+        let rewritten: HashSet<usize> = hashes
+            .iter()
+            .filter_map(|hash| self.index.get(hash).map(|(pack, _)| *pack))
+            .collect();
+        if rewritten.is_empty() {
+            return Ok(());
+        }
+
+        let mut kept: Vec<_> = self
+            .index
+            .values()
+            .filter(|(pack, entry)| rewritten.contains(pack) && !removed.contains(&entry.hash))
+            .collect();
+        kept.sort_unstable_by_key(|(pack, entry)| (*pack, entry.offset));
+        let kept = kept
+            .into_iter()
+            .map(|(pack, entry)| {
+                let data = read_chunk(&mut File::open(&self.packs[*pack])?, entry)?;
+                Ok((entry.hash.clone(), data))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        // Forgets the rewritten pack files, and renumbers the other ones.
+        let old_packs = std::mem::take(&mut self.packs);
+        let mut positions = vec![None; old_packs.len()];
+        let mut deleted = vec![];
+        for (position, path) in old_packs.into_iter().enumerate() {
+            if rewritten.contains(&position) {
+                deleted.push(path);
+            } else {
+                positions[position] = Some(self.packs.len());
+                self.packs.push(path);
+            }
+        }
+        self.index.retain(|_, (pack, _)| {
+            let position = positions[*pack];
+            if let Some(position) = position {
+                *pack = position;
+            }
+            position.is_some()
+        });
+
+        for (hash, data) in kept {
+            _ = self.put(&hash, &data)?;
+        }
+        self.flush()?;
+
+        // A new pack file may have the content, and so the id, of an old one.
+        for path in deleted {
+            if !self.packs.contains(&path) {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::chunk_store::to_hex;
+    use crate::pack::*;
+    use crate::test_utils::{hash, pseudo_random};
+    use crate::{store_chunks, ChunkDataIter};
+
+    #[test]
+    fn pack_round_trip() {
+        let mut writer = PackWriter::new(vec![]);
+        writer.add([1u8; 4], b"hello").unwrap();
+        writer.add([2u8; 4], b"world!").unwrap();
+        let (pack, entries) = writer.finish().unwrap();
+        assert_eq!(entries[1].offset, 5);
+
+        let mut reader = PackReader::<_, [u8; 4]>::open(Cursor::new(&pack)).unwrap();
+        assert_eq!(reader.entries(), entries);
+        assert_eq!(
+            reader.get(&[2; 4]).unwrap().as_deref(),
+            Some(&b"world!"[..])
+        );
+        assert_eq!(reader.get(&[1; 4]).unwrap().as_deref(), Some(&b"hello"[..]));
+        assert_eq!(reader.get(&[3; 4]).unwrap(), None);
+
+        let mut corrupted = pack.clone();
+        corrupted[11] = b'X';
+        let err = PackReader::<_, [u8; 4]>::open(Cursor::new(&corrupted)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let truncated = &pack[1..];
+        assert!(PackReader::<_, [u8; 4]>::open(Cursor::new(truncated)).is_err());
+    }
+
+    #[test]
+    fn pack_store() {
+        let root = std::env::temp_dir().join(format!("cdc-pack-store-{}", std::process::id()));
+        let data = pseudo_random(50_000);
+
+        let mut store = PackStore::open(&root, 8 * 1024, hash).unwrap();
+        let chunks = ChunkDataIter::custom_new(&data[..], 4, |x| x & 0xff == 0xff);
+        let (hashed_chunks, _) = store_chunks(&mut store, chunks, hash).unwrap();
+        let (chunk, chunk_hash) = hashed_chunks[hashed_chunks.len() - 1];
+        assert_eq!(
+            store.get(&chunk_hash).unwrap().unwrap().len() as u64,
+            chunk.size
+        );
+        store.flush().unwrap();
+        assert!(store.packs().len() > 1);
+        for path in store.packs() {
+            let id = to_hex(&hash(&fs::read(path).unwrap()));
+            assert_eq!(path, &root.join(&id[..2]).join(&id));
+        }
+
+        let store = PackStore::open(&root, 8 * 1024, hash).unwrap();
+        for (chunk, chunk_hash) in &hashed_chunks {
+            let chunk_data = store.get(chunk_hash).unwrap().unwrap();
+            assert_eq!(chunk_data.len() as u64, chunk.size);
+            assert_eq!(hash(&chunk_data), *chunk_hash);
+        }
+
+        let mut store = store;
+        let mut hashes: Vec<_> = hashed_chunks.iter().map(|(_, hash)| *hash).collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+        store.remove(&hashes[..hashes.len() / 2]).unwrap();
+        drop(store);
+        let store = PackStore::open(&root, 8 * 1024, hash).unwrap();
+        assert_eq!(
+            store.hashes().unwrap().len(),
+            hashes.len() - hashes.len() / 2
+        );
+        assert!(!store.contains(&hashes[0]).unwrap());
+        for chunk_hash in &hashes[hashes.len() / 2..] {
+            let chunk_data = store.get(chunk_hash).unwrap().unwrap();
+            assert_eq!(hash(&chunk_data), *chunk_hash);
+        }
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn flush_on_drop() {
+        let root = std::env::temp_dir().join(format!("cdc-pack-drop-{}", std::process::id()));
+
+        let mut store = PackStore::open(&root, 8 * 1024, hash).unwrap();
+        assert!(store.put(&hash(b"hello"), b"hello").unwrap());
+        assert!(store.packs().is_empty());
+        drop(store);
+
+        let store = PackStore::open(&root, 8 * 1024, hash).unwrap();
+        assert_eq!(store.packs().len(), 1);
+        assert_eq!(
+            store.get(&hash(b"hello")).unwrap().as_deref(),
+            Some(&b"hello"[..])
+        );
+        drop(store);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
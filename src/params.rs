@@ -0,0 +1,108 @@
+#[cfg(feature = "std")]
+use std::io::Read;
+
+#[cfg(feature = "std")]
+use crate::ChunkDataIter;
+use crate::{HashToLevel, SeparatorIter};
+
+/// The parameters of the chunking of a stream and of the tree built from its chunks.
+///
+/// # Note
+///
+/// The separators are found with a predicate checking that the `boundary_nb_bits` lowest bits
+/// of the rolling hash are all set, like the predicate used by [`SeparatorIter::new`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChunkerParams {
+    /// The number of bits of the separator size, i.e. of the rolling hash window size.
+    pub separator_size_nb_bits: u32,
+
+    /// The number of bits of the hash checked to find a separator.
+    pub boundary_nb_bits: u32,
+
+    /// The number of bits of the level 0, see [`HashToLevel::custom_new`].
+    pub lvl0_nb_bits: u32,
+
+    /// The number of bits of the level up, see [`HashToLevel::custom_new`].
+    pub lvlup_nb_bits: u32,
+
+    /// The maximum number of children a node can have, 0 for no limit.
+    pub max_node_children: usize,
+}
+
+impl Default for ChunkerParams {
+    fn default() -> Self {
+        Self {
+            separator_size_nb_bits: 6,
+            boundary_nb_bits: 13,
+            lvl0_nb_bits: 13,
+            lvlup_nb_bits: 3,
+            max_node_children: 0,
+        }
+    }
+}
+
+impl ChunkerParams {
+    /// Returns `true` if the parameters can be used to chunk a stream.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        (1..32).contains(&self.separator_size_nb_bits)
+            && (1..64).contains(&self.boundary_nb_bits)
+            && self.lvl0_nb_bits < 64
+            && (1..64).contains(&self.lvlup_nb_bits)
+            && self.max_node_children != 1
+    }
+
+    /// Returns the window size of the rolling hash.
+    ///
+    /// # Note
+    ///
+    /// It is also the minimum size of a chunk, except for the last chunk of a stream.
+    #[must_use]
+    pub fn window_size(&self) -> u64 {
+        1 << self.separator_size_nb_bits
+    }
+
+    /// Returns the highest level a chunk can have.
+    #[must_use]
+    pub fn max_level(&self) -> usize {
+        ((64 - self.lvl0_nb_bits) / self.lvlup_nb_bits) as usize
+    }
+
+    /// Returns the predicate used to determine if a separator is a separator boundary.
+    pub fn predicate(&self) -> impl Fn(u64) -> bool + Copy {
+        let bitmask = (1u64 << self.boundary_nb_bits) - 1;
+        move |x| x & bitmask == bitmask
+    }
+
+    /// Returns the converter of separator hashes to levels.
+    #[must_use]
+    pub fn hash_to_level(&self) -> HashToLevel {
+        HashToLevel::custom_new(self.lvl0_nb_bits, self.lvlup_nb_bits)
+    }
+
+    /// Creates a new `SeparatorIter` with these parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `iter` - The iterator to separate.
+    pub fn separators<I>(&self, iter: I) -> SeparatorIter<I, impl Fn(u64) -> bool + Copy>
+    where
+        I: Iterator<Item = u8>,
+    {
+        SeparatorIter::custom_new(iter, self.separator_size_nb_bits, self.predicate())
+    }
+
+    #[cfg(feature = "std")]
+    /// Creates a new `ChunkDataIter` with these parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader of the data to chunk.
+    pub fn chunk_data<R>(&self, reader: R) -> ChunkDataIter<R, impl Fn(u64) -> bool + Copy>
+    where
+        R: Read,
+    {
+        ChunkDataIter::custom_new(reader, self.separator_size_nb_bits, self.predicate())
+    }
+}
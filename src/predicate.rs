@@ -0,0 +1,312 @@
+/// A predicate determining if a separator is a chunk boundary, from its hash and the size of the
+/// chunk it would end.
+///
+/// It is implemented for the closures `Fn(u64) -> bool`, which only check the hash, and by
+/// [`Mask`], [`Threshold`] and [`NormalizedMasks`], which know their expected chunk size.
+pub trait BoundaryPredicate {
+    /// Returns `true` if a separator is a chunk boundary.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the separator.
+    /// * `size` - The size of the chunk the separator would end.
+    fn is_boundary(&self, hash: u64, size: u64) -> bool;
+
+    /// Returns the expected average size of the chunks, if it is known.
+    ///
+    /// It supposes that the predicate is checked at every size from 1 byte, ignoring the minimum
+    /// size imposed by the iterator, e.g. the window size of [`SeparatorIter`](crate::SeparatorIter).
+    fn expected_chunk_size(&self) -> Option<f64> {
+        None
+    }
+}
+
+impl<F: Fn(u64) -> bool> BoundaryPredicate for F {
+    #[inline]
+    fn is_boundary(&self, hash: u64, _size: u64) -> bool {
+        self(hash)
+    }
+}
+
+/// A reference to a predicate, used as a predicate.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ByRef<'a, P>(pub(crate) &'a P);
+
+impl<P: BoundaryPredicate> BoundaryPredicate for ByRef<'_, P> {
+    #[inline]
+    fn is_boundary(&self, hash: u64, size: u64) -> bool {
+        self.0.is_boundary(hash, size)
+    }
+
+    fn expected_chunk_size(&self) -> Option<f64> {
+        self.0.expected_chunk_size()
+    }
+}
+
+/// A predicate checking that some bits of the hash are all set, like the predicate used by
+/// [`SeparatorIter::new`](crate::SeparatorIter::new).
+///
+/// The hash of [`Rabin64`](crate::Rabin64) has fewer bits than its modulo polynom's degree,
+/// the mask must only use these bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mask {
+    /// The bits which must be set.
+    mask: u64,
+}
+
+impl Mask {
+    /// Creates a new `Mask` checking the lowest bits of the hash, with an expected chunk size of
+    /// `2^nb_bits`.
+    ///
+    /// # Arguments
+    ///
+    /// * `nb_bits` - The number of bits checked.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `nb_bits` is 64 or more.
+    #[must_use]
+    pub fn new(nb_bits: u32) -> Self {
+        Self::with_offset(nb_bits, 0)
+    }
+
+    /// Creates a new `Mask` checking the bits of the hash after the `offset_nb_bits` lowest ones,
+    /// with an expected chunk size of `2^nb_bits`.
+    ///
+    /// # Arguments
+    ///
+    /// * `nb_bits` - The number of bits checked.
+    /// * `offset_nb_bits` - The number of lowest bits not checked.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `nb_bits + offset_nb_bits` is 64 or more.
+    #[must_use]
+    pub fn with_offset(nb_bits: u32, offset_nb_bits: u32) -> Self {
+        assert!(
+            nb_bits + offset_nb_bits < 64,
+            "the mask must fit in the hash"
+        );
+        Self {
+            mask: ((1 << nb_bits) - 1) << offset_nb_bits,
+        }
+    }
+
+    /// Returns the probability that a hash satisfies the mask.
+    fn probability(self) -> f64 {
+        powi(0.5, u64::from(self.mask.count_ones()))
+    }
+}
+
+impl BoundaryPredicate for Mask {
+    #[inline]
+    fn is_boundary(&self, hash: u64, _size: u64) -> bool {
+        hash & self.mask == self.mask
+    }
+
+    fn expected_chunk_size(&self) -> Option<f64> {
+        Some(1.0 / self.probability())
+    }
+}
+
+/// A predicate checking that the lowest 32 bits of the hash are below a threshold, for any
+/// expected chunk size, not only powers of two.
+///
+/// The hashes of its boundaries don't have their lowest bits set: they all have the level 0
+/// with a [`HashToLevel`](crate::HashToLevel).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Threshold {
+    /// The threshold of the lowest 32 bits of the hash.
+    threshold: u64,
+}
+
+impl Threshold {
+    /// Creates a new `Threshold` with an expected chunk size of about `target_size`.
+    ///
+    /// # Arguments
+    ///
+    /// * `target_size` - The expected chunk size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `target_size` is `0` or more than `2^32`.
+    #[must_use]
+    pub fn new(target_size: u64) -> Self {
+        assert!(
+            (1..=1 << 32).contains(&target_size),
+            "the target size must be in 1..=2^32"
+        );
+        Self {
+            threshold: (1 << 32) / target_size,
+        }
+    }
+}
+
+impl BoundaryPredicate for Threshold {
+    #[inline]
+    fn is_boundary(&self, hash: u64, _size: u64) -> bool {
+        hash & 0xffff_ffff < self.threshold
+    }
+
+    #[allow(clippy::cast_precision_loss)] // The threshold has at most 33 bits.
+    fn expected_chunk_size(&self) -> Option<f64> {
+        Some((1u64 << 32) as f64 / self.threshold as f64)
+    }
+}
+
+/// A predicate with a stricter mask before a normal size and a looser one after it.
+///
+/// As in the normalized chunking of `FastCDC`, the chunk sizes are closer to the normal size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NormalizedMasks {
+    /// The size from which the loose mask is used.
+    normal_size: u64,
+
+    /// The mask used for the chunks smaller than the normal size.
+    strict: Mask,
+
+    /// The mask used for the chunks of the normal size or larger.
+    loose: Mask,
+}
+
+impl NormalizedMasks {
+    /// Creates a new `NormalizedMasks`, with masks of `log2(normal_size) ± level` bits.
+    ///
+    /// # Arguments
+    ///
+    /// * `normal_size` - The size from which the loose mask is used, rounded down to a power of
+    ///   two for the number of bits of the masks.
+    /// * `level` - The normalization level, usually 1 to 3.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `normal_size` is `0`, or if `level` is more than `log2(normal_size)` or too
+    /// large for the strict mask to fit in the hash.
+    #[must_use]
+    pub fn new(normal_size: u64, level: u32) -> Self {
+        assert!(normal_size > 0, "the normal size must not be 0");
+        let nb_bits = normal_size.ilog2();
+        assert!(
+            level <= nb_bits,
+            "the level must not exceed log2(normal_size)"
+        );
+        Self::custom_new(
+            normal_size,
+            Mask::new(nb_bits + level),
+            Mask::new(nb_bits - level),
+        )
+    }
+
+    /// Creates a new `NormalizedMasks` with custom masks.
+    ///
+    /// # Arguments
+    ///
+    /// * `normal_size` - The size from which the loose mask is used.
+    /// * `strict` - The mask used for the chunks smaller than the normal size.
+    /// * `loose` - The mask used for the chunks of the normal size or larger.
+    #[must_use]
+    pub fn custom_new(normal_size: u64, strict: Mask, loose: Mask) -> Self {
+        Self {
+            normal_size,
+            strict,
+            loose,
+        }
+    }
+}
+
+impl BoundaryPredicate for NormalizedMasks {
+    #[inline]
+    fn is_boundary(&self, hash: u64, size: u64) -> bool {
+        if size < self.normal_size {
+            self.strict.is_boundary(hash, size)
+        } else {
+            self.loose.is_boundary(hash, size)
+        }
+    }
+
+    fn expected_chunk_size(&self) -> Option<f64> {
+        // The probability that no boundary is found before the normal size.
+        let strict = self.strict.probability();
+        let no_strict_boundary = powi(1.0 - strict, self.normal_size.saturating_sub(1));
+        Some((1.0 - no_strict_boundary) / strict + no_strict_boundary / self.loose.probability())
+    }
+}
+
+/// Raises a number to an integer power, by squaring, as `f64::powi` needs `std`.
+fn powi(mut base: f64, mut exp: u64) -> f64 {
+    let mut result = 1.0;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result *= base;
+        }
+        base *= base;
+        exp >>= 1;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{random_data, xorshift, SEED};
+    use crate::*;
+
+    /// Returns the average size of the chunks of pseudo-random data with a predicate, checked at
+    /// every size.
+    #[allow(clippy::cast_precision_loss)]
+    fn average_chunk_size(predicate: &dyn BoundaryPredicate) -> f64 {
+        let (mut nb_chunks, mut size) = (0u64, 0);
+        for state in xorshift(SEED).take(4_000_000) {
+            size += 1;
+            if predicate.is_boundary(state, size) {
+                nb_chunks += 1;
+                size = 0;
+            }
+        }
+        4_000_000.0 / nb_chunks as f64
+    }
+
+    #[test]
+    fn predicates_in_iterators() {
+        let data = random_data(100_000, SEED);
+
+        let closure = SeparatorIter::custom_new(data.iter().copied(), 5, |x: u64| x & 0xff == 0xff);
+        let mask = SeparatorIter::custom_new(data.iter().copied(), 5, Mask::new(8));
+        assert!(closure.eq(mask));
+
+        let normalized = NormalizedMasks::new(256, 2);
+        let separators = SeparatorIter::custom_new(data.iter().copied(), 5, normalized);
+        let mut stats = ChunkStats::new(normalized);
+        let mut nb_chunks = 0;
+        for chunk in stats.inspect(ChunkIter::new(separators, data.len() as u64)) {
+            let strict = chunk.separator_hash & 0x3ff == 0x3ff;
+            assert!(chunk.size >= 256 || strict || chunk.index == data.len() as u64);
+            nb_chunks += 1;
+        }
+        assert!(nb_chunks > 100);
+        assert!(stats.summary().forced_cut_ratio < f64::EPSILON);
+    }
+
+    #[test]
+    fn expected_chunk_sizes() {
+        let predicates: [&dyn BoundaryPredicate; 4] = [
+            &Mask::new(10),
+            &Mask::with_offset(10, 20),
+            &Threshold::new(3_000),
+            &NormalizedMasks::new(2_048, 2),
+        ];
+        let expected = [1_024.0, 1_024.0, 3_000.0, 2_210.0];
+        for (predicate, expected) in predicates.iter().zip(expected) {
+            let expected_size = predicate.expected_chunk_size().unwrap();
+            assert!((expected_size - expected).abs() < 1.0, "{expected_size}");
+            let average = average_chunk_size(*predicate);
+            assert!((average / expected - 1.0).abs() < 0.05, "{average}");
+        }
+
+        assert!((|hash: u64| hash == 0).expected_chunk_size().is_none());
+        assert!(Mask::with_offset(2, 4).is_boundary(0b11_0000, 0));
+        assert!(!Mask::with_offset(2, 4).is_boundary(0b1111, 0));
+    }
+}
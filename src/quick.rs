@@ -0,0 +1,409 @@
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+
+use crate::{BoundaryPredicate, Rabin64, RollingHash64, Separator};
+
+/// The chunks previously seen by [`QuickSeparatorIter`]s, to jump to their end when they are
+/// seen again.
+///
+/// A chunk is known by its first bytes, which fill the rolling hash window before any
+/// separator can be found, with its data and the hash of its separator. It must only be used
+/// with iterators having the same window size and predicate.
+///
+/// The data of the known chunks is kept up to a total size, the capacity, the oldest chunks
+/// being forgotten first. The chunks larger than a maximum size are not kept: the iterators
+/// don't buffer more than this size of a chunk, and hash the rest of it normally.
+#[derive(Debug, Clone)]
+pub struct KnownChunks {
+    /// The last chunk seen, indexed by its first bytes.
+    chunks: BTreeMap<Vec<u8>, KnownChunk>,
+
+    /// The first bytes of the chunks seen, from the oldest to the newest, with their number.
+    /// A chunk replaced by a newer one with the same first bytes stays until it is evicted.
+    order: VecDeque<(Vec<u8>, u64)>,
+
+    /// The number of chunks seen so far.
+    nb_seen: u64,
+
+    /// The total size of the data of the known chunks.
+    size: usize,
+
+    /// The maximum total size of the data of the known chunks.
+    capacity: usize,
+
+    /// The maximum size of a known chunk.
+    max_chunk_size: usize,
+}
+
+/// A chunk previously seen.
+#[derive(Debug, Clone)]
+struct KnownChunk {
+    /// The data of the chunk.
+    data: Vec<u8>,
+
+    /// The hash of the separator of the chunk.
+    hash: u64,
+
+    /// The number of the chunk, in the order they were seen.
+    number: u64,
+}
+
+impl Default for KnownChunks {
+    fn default() -> Self {
+        Self::with_limits(Self::DEFAULT_CAPACITY, Self::DEFAULT_MAX_CHUNK_SIZE)
+    }
+}
+
+impl KnownChunks {
+    /// The default maximum total size of the data of the known chunks.
+    pub const DEFAULT_CAPACITY: usize = 64 * 1024 * 1024;
+
+    /// The default maximum size of a known chunk.
+    pub const DEFAULT_MAX_CHUNK_SIZE: usize = 1024 * 1024;
+
+    /// Creates a new, empty `KnownChunks`, with the default limits.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new, empty `KnownChunks`.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The maximum total size of the data of the known chunks.
+    /// * `max_chunk_size` - The maximum size of a known chunk.
+    #[must_use]
+    pub fn with_limits(capacity: usize, max_chunk_size: usize) -> Self {
+        Self {
+            chunks: BTreeMap::new(),
+            order: VecDeque::new(),
+            nb_seen: 0,
+            size: 0,
+            capacity,
+            max_chunk_size,
+        }
+    }
+
+    /// Returns the number of known chunks.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Returns `true` if no chunk is known.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Returns the total size of the data of the known chunks.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Forgets all the chunks.
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.order.clear();
+        self.size = 0;
+    }
+
+    /// Adds a chunk, replacing the one with the same first bytes and evicting the oldest ones
+    /// beyond the capacity.
+    fn insert(&mut self, front: &[u8], data: Vec<u8>, hash: u64) {
+        let number = self.nb_seen;
+        self.nb_seen += 1;
+        self.size += data.len();
+        let chunk = KnownChunk { data, hash, number };
+        if let Some(old) = self.chunks.insert(front.to_vec(), chunk) {
+            self.size -= old.data.len();
+        }
+        self.order.push_back((front.to_vec(), number));
+
+        while self.size > self.capacity {
+            let Some((front, number)) = self.order.pop_front() else {
+                break;
+            };
+            self.remove(&front, number);
+        }
+        // Drops the replaced chunks when they outnumber the known ones.
+        if self.order.len() > 2 * self.chunks.len() + 16 {
+            let chunks = &self.chunks;
+            self.order.retain(|(front, number)| {
+                chunks
+                    .get(front)
+                    .is_some_and(|chunk| chunk.number == *number)
+            });
+        }
+    }
+
+    /// Removes a chunk, unless it was replaced by a newer one.
+    fn remove(&mut self, front: &[u8], number: u64) {
+        if self
+            .chunks
+            .get(front)
+            .is_some_and(|chunk| chunk.number == number)
+        {
+            if let Some(chunk) = self.chunks.remove(front) {
+                self.size -= chunk.data.len();
+            }
+        }
+    }
+}
+
+/// An iterator that separates data like [`SeparatorIter`](crate::SeparatorIter), jumping over
+/// the chunks it already knows, as in `QuickCDC`.
+///
+/// A separator only depends on the bytes since the previous one. When the first bytes of a
+/// chunk are the ones of a known chunk, the bytes up to its expected end are read without
+/// being hashed with the rolling hash: they are compared with the data of the known chunk,
+/// and the chunk is hashed normally if they don't match. The separators are always the same
+/// as the ones of `SeparatorIter`.
+#[derive(Debug)]
+pub struct QuickSeparatorIter<'a, I, F> {
+    /// The iterator to separate.
+    iter: I,
+
+    /// The predicate used to determine if a separator is a separator boundary.
+    predicate: F,
+
+    /// The rolling hash.
+    rabin: Rabin64,
+
+    /// The number of bytes used to prefill the window of the rolling hash.
+    prefill_size: usize,
+
+    /// The index of the start of the current chunk.
+    index: u64,
+
+    /// The bytes read from the start of the current chunk, up to the maximum size of a known
+    /// chunk.
+    chunk: Vec<u8>,
+
+    /// The bytes to separate again, before reading from the iterator.
+    replay: VecDeque<u8>,
+
+    /// The chunks previously seen.
+    known: &'a mut KnownChunks,
+
+    /// The number of bytes jumped over without being hashed.
+    skipped_bytes: u64,
+}
+
+impl<'a, I> QuickSeparatorIter<'a, I, fn(u64) -> bool>
+where
+    I: Iterator<Item = u8>,
+{
+    /// Creates a new `QuickSeparatorIter`, finding the same separators as
+    /// [`SeparatorIter::new`](crate::SeparatorIter::new).
+    ///
+    /// # Arguments
+    ///
+    /// * `iter` - The iterator to separate.
+    /// * `known` - The chunks previously seen, updated with the new ones.
+    pub fn new(iter: I, known: &'a mut KnownChunks) -> Self {
+        #[inline]
+        fn default_predicate(x: u64) -> bool {
+            const BITMASK: u64 = (1u64 << 13) - 1;
+            x & BITMASK == BITMASK
+        }
+
+        Self::custom_new(iter, known, 6, default_predicate)
+    }
+}
+
+impl<'a, I, F> QuickSeparatorIter<'a, I, F>
+where
+    I: Iterator<Item = u8>,
+    F: BoundaryPredicate,
+{
+    /// Creates a new `QuickSeparatorIter`, finding the same separators as
+    /// [`SeparatorIter::custom_new`](crate::SeparatorIter::custom_new).
+    ///
+    /// # Arguments
+    ///
+    /// * `iter` - The iterator to separate.
+    /// * `known` - The chunks previously seen, updated with the new ones.
+    /// * `separator_size_nb_bits` - The number of bits of the separator size.
+    /// * `predicate` - The predicate used to determine if a separator is a separator boundary.
+    pub fn custom_new(
+        iter: I,
+        known: &'a mut KnownChunks,
+        separator_size_nb_bits: u32,
+        predicate: F,
+    ) -> Self {
+        Self {
+            iter,
+            predicate,
+            rabin: Rabin64::new(separator_size_nb_bits),
+            prefill_size: (1 << separator_size_nb_bits) - 1,
+            index: 0,
+            chunk: Vec::new(),
+            replay: VecDeque::new(),
+            known,
+            skipped_bytes: 0,
+        }
+    }
+
+    /// Returns the number of bytes jumped over without being hashed.
+    #[must_use]
+    pub fn skipped_bytes(&self) -> u64 {
+        self.skipped_bytes
+    }
+
+    /// Returns the next byte to separate.
+    fn next_byte(&mut self) -> Option<u8> {
+        self.replay.pop_front().or_else(|| self.iter.next())
+    }
+
+    /// Jumps to the end of the known chunk starting with the same bytes as the current one,
+    /// and returns its separator if the data matches.
+    fn jump(&mut self) -> Option<Separator> {
+        let known = self.known.chunks.get(&self.chunk[..self.prefill_size])?;
+        let size = known.data.len();
+        if size <= self.prefill_size {
+            return None;
+        }
+        let missing = size.saturating_sub(self.chunk.len());
+        let replayed = missing.min(self.replay.len());
+        self.chunk.extend(self.replay.drain(..replayed));
+        self.chunk
+            .extend(self.iter.by_ref().take(missing - replayed));
+        if self.chunk.get(..size) != Some(&known.data[..]) {
+            return None;
+        }
+
+        let hash = known.hash;
+        self.skipped_bytes += (size - self.prefill_size) as u64;
+        Some(self.cut(size, hash))
+    }
+
+    /// Ends the current chunk after `size` bytes, keeping the next bytes read to separate them
+    /// again.
+    fn cut(&mut self, size: usize, hash: u64) -> Separator {
+        if size < self.chunk.len() {
+            for byte in self.chunk.drain(size..).rev() {
+                self.replay.push_front(byte);
+            }
+        }
+        self.chunk.clear();
+        self.index += size as u64;
+        Separator {
+            index: self.index,
+            hash,
+        }
+    }
+}
+
+impl<I, F> Iterator for QuickSeparatorIter<'_, I, F>
+where
+    I: Iterator<Item = u8>,
+    F: BoundaryPredicate,
+{
+    type Item = Separator;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.chunk.len() < self.prefill_size {
+            let byte = self.next_byte()?;
+            self.chunk.push(byte);
+        }
+
+        if let Some(separator) = self.jump() {
+            return Some(separator);
+        }
+
+        let front = &self.chunk[..self.prefill_size];
+        _ = self
+            .rabin
+            .reset_and_prefill_window(&mut front.iter().copied());
+        let max_size = self.known.max_chunk_size;
+        let mut size = self.prefill_size;
+        loop {
+            let byte = if size < self.chunk.len() {
+                self.chunk[size]
+            } else {
+                let byte = self.next_byte()?;
+                // The rest of a chunk larger than a known chunk is only hashed.
+                if self.chunk.len() < max_size {
+                    self.chunk.push(byte);
+                }
+                byte
+            };
+            self.rabin.slide(byte);
+            size += 1;
+            if self.predicate.is_boundary(self.rabin.hash, size as u64) {
+                let hash = self.rabin.hash;
+                if size <= max_size {
+                    let data = self.chunk[..size].to_vec();
+                    self.known
+                        .insert(&self.chunk[..self.prefill_size], data, hash);
+                }
+                return Some(self.cut(size, hash));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{random_data, SEED};
+    use crate::*;
+
+    #[test]
+    fn quick_separators() {
+        // Not periodic, so that the starts of the chunks are distinct.
+        let old = random_data(200_000, SEED);
+        let mut new = old.clone();
+        new[50_000..50_010].copy_from_slice(b"0123456789");
+        _ = new.splice(120_000..120_000, b"inserted bytes".iter().copied());
+        let predicate = |x: u64| x & 0x3ff == 0x3ff;
+
+        let mut known = KnownChunks::new();
+        for data in [&old, &new, &old] {
+            let expected: Vec<Separator> =
+                SeparatorIter::custom_new(data.iter().copied(), 5, predicate).collect();
+            assert!(expected.len() > 50);
+
+            let mut separators =
+                QuickSeparatorIter::custom_new(data.iter().copied(), &mut known, 5, predicate);
+            assert_eq!(separators.by_ref().collect::<Vec<_>>(), expected);
+            if data == &new {
+                assert!(separators.skipped_bytes() > 150_000);
+            }
+        }
+        assert!(!known.is_empty());
+
+        let mut separators =
+            QuickSeparatorIter::custom_new(old.iter().copied(), &mut known, 5, predicate);
+        let expected = SeparatorIter::custom_new(old.iter().copied(), 5, predicate).count();
+        assert_eq!(separators.by_ref().count(), expected);
+        assert!(separators.skipped_bytes() > 190_000);
+    }
+
+    #[test]
+    fn limits() {
+        let data = random_data(200_000, SEED);
+        let predicate = |x: u64| x & 0x3ff == 0x3ff;
+        let expected: Vec<Separator> =
+            SeparatorIter::custom_new(data.iter().copied(), 5, predicate).collect();
+
+        // The oldest chunks are evicted.
+        let mut known = KnownChunks::with_limits(20_000, 4_000);
+        for _ in 0..2 {
+            let separators =
+                QuickSeparatorIter::custom_new(data.iter().copied(), &mut known, 5, predicate);
+            assert_eq!(separators.collect::<Vec<_>>(), expected);
+            assert!(known.size() <= 20_000);
+            assert!(known.len() > 5);
+        }
+
+        // Without separator, the chunk is only buffered up to the maximum size.
+        let zeros = vec![0; 100_000];
+        let mut separators =
+            QuickSeparatorIter::custom_new(zeros.iter().copied(), &mut known, 5, predicate);
+        assert_eq!(separators.next(), None);
+        assert_eq!(separators.chunk.len(), 4_000);
+    }
+}
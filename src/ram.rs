@@ -0,0 +1,128 @@
+use crate::Separator;
+
+/// An iterator that separates data with the Rapid Asymmetric Maximum (RAM) algorithm.
+///
+/// The first `window_size` bytes of a chunk establish a maximum byte value, and the chunk is
+/// cut after the first following byte which reaches it. As in the original algorithm, a byte
+/// equal to the maximum is a cut point, otherwise a window containing `255` would never end.
+/// A chunk is never smaller than `window_size + 1` bytes, except the last one.
+///
+/// No hash is involved, the hash of the separators is always `0`: when collecting
+/// [`ChunkStats`](crate::ChunkStats), use a predicate accepting any hash, e.g. `|_| true`.
+#[derive(Debug)]
+pub struct RamSeparatorIter<I> {
+    /// The iterator to separate.
+    iter: I,
+
+    /// The size of the window establishing the maximum.
+    window_size: u64,
+
+    /// The number of bytes read.
+    index: u64,
+
+    /// The number of bytes of the window of the current chunk not read yet.
+    window_remaining: u64,
+
+    /// The maximum byte value in the window of the current chunk.
+    max: u8,
+}
+
+impl<I> RamSeparatorIter<I>
+where
+    I: Iterator<Item = u8>,
+{
+    /// Creates a new `RamSeparatorIter`, with an expected chunk size of about 8 KiB.
+    ///
+    /// # Arguments
+    ///
+    /// * `iter` - The iterator to separate.
+    pub fn new(iter: I) -> Self {
+        // The maximum of a large window is usually 255, found again after about 256 bytes.
+        Self::custom_new(iter, 8_192 - 256)
+    }
+
+    /// Creates a new `RamSeparatorIter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `iter` - The iterator to separate.
+    /// * `window_size` - The size of the window establishing the maximum.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window_size` is `0`.
+    pub fn custom_new(iter: I, window_size: u64) -> Self {
+        assert!(window_size > 0, "the window size must not be 0");
+        Self {
+            iter,
+            window_size,
+            index: 0,
+            window_remaining: window_size,
+            max: 0,
+        }
+    }
+}
+
+impl<I> Iterator for RamSeparatorIter<I>
+where
+    I: Iterator<Item = u8>,
+{
+    type Item = Separator;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        for byte in self.iter.by_ref() {
+            self.index += 1;
+            if self.window_remaining > 0 {
+                self.window_remaining -= 1;
+                self.max = self.max.max(byte);
+            } else if byte >= self.max {
+                self.window_remaining = self.window_size;
+                self.max = 0;
+                return Some(Separator {
+                    index: self.index,
+                    hash: 0,
+                });
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::pseudo_random;
+    use crate::*;
+
+    /// Returns the size of the first chunk of `data`, as described in the RAM paper.
+    fn reference_chunk_size(data: &[u8], window_size: usize) -> usize {
+        let Some(max) = data.iter().take(window_size).max() else {
+            return 0;
+        };
+        data.iter()
+            .skip(window_size)
+            .position(|byte| byte >= max)
+            .map_or(data.len(), |position| window_size + position + 1)
+    }
+
+    #[test]
+    fn ram_separators() {
+        let data = pseudo_random(200_000);
+        let window_size = 256;
+
+        let separators = RamSeparatorIter::custom_new(data.iter().copied(), window_size as u64);
+        let chunks: Vec<Chunk> = ChunkIter::new(separators, data.len() as u64).collect();
+        assert!(chunks.len() > 100);
+
+        let mut offset = 0;
+        for chunk in &chunks {
+            let size = reference_chunk_size(&data[offset..], window_size);
+            assert_eq!(chunk.size, size as u64);
+            assert_eq!(chunk.separator_hash, 0);
+            offset += size;
+            assert!(size > window_size || offset == data.len());
+        }
+        assert_eq!(offset, data.len());
+    }
+}
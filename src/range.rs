@@ -0,0 +1,193 @@
+use alloc::{vec, vec::Vec};
+use core::ops::Range;
+
+use crate::{Child, ChildKind, MissingNodeError, NodeStore};
+
+/// A part of a chunk, used to cover a byte range of a stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChunkSlice<H> {
+    /// The hash of the chunk.
+    pub hash: H,
+
+    /// The offset of the chunk in the stream.
+    pub chunk_offset: u64,
+
+    /// The offset of the slice in the chunk.
+    pub offset: u64,
+
+    /// The length of the slice.
+    pub len: u64,
+}
+
+/// Finds the minimal sequence of chunks covering a byte range of a stream.
+///
+/// The tree is descended from its root, only visiting the nodes overlapping the range,
+/// using the sizes of their children. The sizes are not trusted: the descent stops at the
+/// first chunk or node which would end after `u64::MAX`, which only an invalid tree can have.
+///
+/// # Arguments
+///
+/// * `store` - The store containing the nodes of the tree.
+/// * `root` - The root of the tree, as returned by [`NodeIter::root`](crate::NodeIter::root).
+/// * `range` - The byte range, it is truncated to the size of the stream. An empty or
+///   reversed range is covered by no chunk.
+///
+/// # Errors
+///
+/// Returns a [`MissingNodeError`] if a node overlapping the range is not in the store.
+pub fn find_range<S, H>(
+    store: &S,
+    root: Child<H>,
+    range: Range<u64>,
+) -> Result<Vec<ChunkSlice<H>>, MissingNodeError<H>>
+where
+    S: NodeStore<H>,
+{
+    let mut slices = vec![];
+    if range.start >= range.end {
+        return Ok(slices);
+    }
+
+    // The children still to visit with their offset in the stream, the next one being at the end.
+    let mut stack = vec![(root, 0u64)];
+    while let Some((child, child_offset)) = stack.pop() {
+        let Some(child_end) = child_offset.checked_add(child.size) else {
+            // The next children are even further in the stream.
+            break;
+        };
+        if child_end <= range.start || child_offset >= range.end {
+            continue;
+        }
+
+        match child.kind {
+            ChildKind::Leaf => {
+                let start = range.start.max(child_offset);
+                let end = range.end.min(child_end);
+                slices.push(ChunkSlice {
+                    hash: child.hash,
+                    chunk_offset: child_offset,
+                    offset: start - child_offset,
+                    len: end - start,
+                });
+            }
+            ChildKind::Node => {
+                let node = store
+                    .get(&child.hash)
+                    .ok_or(MissingNodeError { hash: child.hash })?;
+                let mut offset = Some(child_offset);
+                let children = node.children.into_iter().map_while(|child| {
+                    let child_offset = offset?;
+                    offset = child_offset.checked_add(child.size);
+                    Some((child, child_offset))
+                });
+                let start = stack.len();
+                stack.extend(children);
+                stack[start..].reverse();
+            }
+        }
+    }
+
+    Ok(slices)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::test_utils::{tree, tree_chunks, TREE_LEVELS};
+    use crate::*;
+
+    #[test]
+    fn range_lookup() {
+        // Chunks of sizes 10, 20, 30, ..., 120.
+        let (nodes, root) = tree(&tree_chunks(|index| (index + 1) * 10));
+        let store: MemoryNodeStore<u64> = nodes.into_iter().collect();
+
+        assert_eq!(find_range(&store, root, 0..0), Ok(vec![]));
+        assert_eq!(find_range(&store, root, 780..1000), Ok(vec![]));
+        assert_eq!(
+            find_range(&store, root, 5..10),
+            Ok(vec![ChunkSlice {
+                hash: 0,
+                chunk_offset: 0,
+                offset: 5,
+                len: 5,
+            }])
+        );
+        assert_eq!(
+            find_range(&store, root, 105..155),
+            Ok(vec![
+                ChunkSlice {
+                    hash: 4,
+                    chunk_offset: 100,
+                    offset: 5,
+                    len: 45,
+                },
+                ChunkSlice {
+                    hash: 5,
+                    chunk_offset: 150,
+                    offset: 0,
+                    len: 5,
+                },
+            ])
+        );
+
+        let all = find_range(&store, root, 0..u64::MAX).unwrap();
+        assert_eq!(all.len(), TREE_LEVELS.len());
+        assert_eq!(all.iter().map(|slice| slice.len).sum::<u64>(), root.size);
+
+        let store = MemoryNodeStore::new();
+        assert_eq!(
+            find_range(&store, root, 0..1),
+            Err(MissingNodeError { hash: root.hash })
+        );
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn empty_ranges() {
+        // The first chunk spans 0..10.
+        let (nodes, root) = tree(&tree_chunks(|index| (index + 1) * 10));
+        let store: MemoryNodeStore<u64> = nodes.into_iter().collect();
+
+        for range in [5..5, 8..5, 10..0, u64::MAX..0] {
+            assert_eq!(find_range(&store, root, range), Ok(vec![]));
+        }
+        // No node is needed to cover an empty range.
+        let store = MemoryNodeStore::new();
+        assert_eq!(find_range(&store, root, 8..5), Ok(vec![]));
+    }
+
+    #[test]
+    fn overflowing_sizes() {
+        // Crafted nodes, whose totals don't match their children.
+        let node = Node {
+            hash: 100,
+            level: 0,
+            size: 20,
+            nb_chunks: 3,
+            children: vec![
+                Child::leaf(1, u64::MAX - 5),
+                Child::leaf(2, 10),
+                Child::leaf(3, 10),
+            ],
+        };
+        let inner = Node::new(101, 1, vec![Child::node(100, 20, 3), Child::leaf(4, 10)]);
+        let store: MemoryNodeStore<u64> = [node, inner].into_iter().collect();
+
+        let expected = ChunkSlice {
+            hash: 1,
+            chunk_offset: 0,
+            offset: 0,
+            len: u64::MAX - 5,
+        };
+        let root = Child::node(100, u64::MAX, 3);
+        assert_eq!(find_range(&store, root, 0..u64::MAX), Ok(vec![expected]));
+        // The chunk after the overflowing one is not visited either.
+        let root = Child::node(101, 30, 4);
+        assert_eq!(find_range(&store, root, 0..u64::MAX), Ok(vec![expected]));
+        assert_eq!(
+            find_range(&store, Child::node(100, 20, 3), u64::MAX - 1..u64::MAX),
+            Ok(vec![])
+        );
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, Read};
+
+use crate::{Child, ChunkStore, LeafIter, NodeStore, TreeError};
+
+/// An error which can occur when restoring a stream from its chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RestoreError<H> {
+    /// A chunk is not in the chunk store.
+    MissingChunk(H),
+
+    /// A node is not in the node store.
+    MissingNode(H),
+
+    /// A node is not at a lower level than its parent in the node store.
+    InvalidLevel(H),
+
+    /// The data of a chunk doesn't match its hash.
+    HashMismatch {
+        /// The hash of the chunk.
+        expected: H,
+
+        /// The hash of the data found in the store.
+        actual: H,
+    },
+}
+
+impl<H: Debug> fmt::Display for RestoreError<H> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingChunk(hash) => write!(f, "chunk {hash:?} not found"),
+            Self::MissingNode(hash) => write!(f, "node {hash:?} not found"),
+            Self::InvalidLevel(hash) => write!(f, "node {hash:?} not below its parent"),
+            Self::HashMismatch { expected, actual } => {
+                write!(f, "chunk {expected:?} has data with hash {actual:?}")
+            }
+        }
+    }
+}
+
+impl<H: Debug> std::error::Error for RestoreError<H> {}
+
+/// A reader restoring a stream from the hashes of its chunks, fetching their data from a store.
+///
+/// The data of each chunk is verified against its hash. A missing chunk or node is reported as
+/// an [`io::ErrorKind::NotFound`] error, and a chunk with a wrong hash or a node above its
+/// parent as an [`io::ErrorKind::InvalidData`] error, all wrapping a [`RestoreError`].
+pub struct ChunkReader<'a, S, H, F> {
+    /// The store containing the chunks.
+    store: &'a S,
+
+    /// The hashes of the chunks still to read.
+    hashes: Box<dyn Iterator<Item = Result<H, RestoreError<H>>> + 'a>,
+
+    /// The function calculating the hash of the data of a chunk.
+    hash: F,
+
+    /// The data of the current chunk.
+    data: Vec<u8>,
+
+    /// The position in the data of the current chunk.
+    pos: usize,
+}
+
+impl<S, H, F> Debug for ChunkReader<'_, S, H, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChunkReader")
+            .field("data_len", &self.data.len())
+            .field("pos", &self.pos)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, S, H, F> ChunkReader<'a, S, H, F>
+where
+    S: ChunkStore<H>,
+    H: 'a,
+    F: Fn(&[u8]) -> H,
+{
+    /// Creates a new `ChunkReader` from the hashes of the chunks.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The store containing the chunks.
+    /// * `hashes` - The hashes of the chunks, in stream order.
+    /// * `hash` - The function calculating the hash of the data of a chunk.
+    pub fn new<I>(store: &'a S, hashes: I, hash: F) -> Self
+    where
+        I: IntoIterator<Item = H>,
+        I::IntoIter: 'a,
+    {
+        Self {
+            store,
+            hashes: Box::new(hashes.into_iter().map(Ok)),
+            hash,
+            data: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Creates a new `ChunkReader` from the root of a tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The store containing the chunks.
+    /// * `nodes` - The store containing the nodes of the tree.
+    /// * `root` - The root of the tree, as returned by [`NodeIter::root`](crate::NodeIter::root).
+    /// * `hash` - The function calculating the hash of the data of a chunk.
+    pub fn from_tree<N>(store: &'a S, nodes: &'a N, root: Child<H>, hash: F) -> Self
+    where
+        N: NodeStore<H>,
+    {
+        let leaves = LeafIter::new(nodes, root);
+        Self {
+            store,
+            hashes: Box::new(leaves.map(|leaf| {
+                leaf.map_err(|err| match err {
+                    TreeError::MissingNode(hash) => RestoreError::MissingNode(hash),
+                    TreeError::InvalidLevel(hash) => RestoreError::InvalidLevel(hash),
+                })
+            })),
+            hash,
+            data: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl<S, H, F> Read for ChunkReader<'_, S, H, F>
+where
+    S: ChunkStore<H>,
+    H: PartialEq + Debug + Send + Sync + 'static,
+    F: Fn(&[u8]) -> H,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.data.len() {
+            let Some(hash) = self.hashes.next() else {
+                return Ok(0);
+            };
+            let hash = hash.map_err(|err| {
+                let kind = match err {
+                    RestoreError::InvalidLevel(_) => io::ErrorKind::InvalidData,
+                    _ => io::ErrorKind::NotFound,
+                };
+                io::Error::new(kind, err)
+            })?;
+            let Some(data) = self.store.get(&hash)? else {
+                let err = RestoreError::MissingChunk(hash);
+                return Err(io::Error::new(io::ErrorKind::NotFound, err));
+            };
+            let actual = (self.hash)(&data);
+            if actual != hash {
+                let err = RestoreError::HashMismatch {
+                    expected: hash,
+                    actual,
+                };
+                return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+            }
+            self.data = data;
+            self.pos = 0;
+        }
+
+        let len = buf.len().min(self.data.len() - self.pos);
+        buf[..len].copy_from_slice(&self.data[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+
+    use crate::test_utils::{hash, periodic_data};
+    use crate::*;
+
+    #[test]
+    fn restore() {
+        let data = periodic_data(30_000);
+        let mut store = MemoryChunkStore::new();
+        let chunks = ChunkDataIter::custom_new(&data[..], 4, |x| x & 0xff == 0xff);
+        let (hashed_chunks, _) = store_chunks(&mut store, chunks, hash).unwrap();
+        let hashes: Vec<_> = hashed_chunks.iter().map(|(_, hash)| *hash).collect();
+
+        let mut restored = vec![];
+        _ = ChunkReader::new(&store, hashes.clone(), hash)
+            .read_to_end(&mut restored)
+            .unwrap();
+        assert_eq!(restored, data);
+
+        let to_level = HashToLevel::custom_new(8, 1);
+        let hashed_chunks = hashed_chunks.iter().map(|(chunk, hash)| HashedChunk {
+            hash: *hash,
+            level: to_level.to_level(chunk.separator_hash),
+            size: chunk.size,
+        });
+        let new_node = |level, children: &Vec<Child<[u8; 8]>>| {
+            let hashes: Vec<u8> = children.iter().flat_map(|child| child.hash).collect();
+            Node::new(hash(&hashes), level, children.clone())
+        };
+        let mut node_iter = NodeIter::new(hashed_chunks, new_node, 0);
+        let nodes: MemoryNodeStore<_> = node_iter.by_ref().collect();
+        let root = node_iter.root().unwrap();
+
+        let mut restored = vec![];
+        _ = ChunkReader::from_tree(&store, &nodes, root, hash)
+            .read_to_end(&mut restored)
+            .unwrap();
+        assert_eq!(restored, data);
+
+        _ = store.put(&[0; 8], b"corrupted").unwrap();
+        let err = ChunkReader::new(&store, [hashes[0], [0; 8]], hash)
+            .read_to_end(&mut vec![])
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.get_ref()
+                .and_then(|err| err.downcast_ref::<RestoreError<[u8; 8]>>()),
+            Some(&RestoreError::HashMismatch {
+                expected: [0; 8],
+                actual: hash(b"corrupted"),
+            })
+        );
+
+        let err = ChunkReader::new(&store, [[1; 8]], hash)
+            .read_to_end(&mut vec![])
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
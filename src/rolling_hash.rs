@@ -1,3 +1,5 @@
+use alloc::{vec, vec::Vec};
+
 use crate::{Polynom, Polynom64};
 
 pub mod constants {
@@ -42,13 +44,22 @@ pub trait RollingHash64 {
 }
 
 /// A rolling hash implementation for 64 bit polynoms from Rabin.
+///
+/// With the `serde` feature, it is serialized as its configuration, without its current state.
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "Rabin64Config", into = "Rabin64Config")
+)]
 pub struct Rabin64 {
     // Configuration
     /// Window size.
     window_size: usize, // The size of the data window used in the hash calculation.
     /// Window size mask.
     window_size_mask: usize, // = window_size - 1, supposing that it is an exponent of 2.
+    /// Modulo polynom.
+    mod_polynom: Polynom64,
 
     // Precalculations
     /// The number of bits to shift the polynom to the left.
@@ -119,6 +130,7 @@ impl Rabin64 {
         Self {
             window_size,
             window_size_mask: window_size - 1,
+            mod_polynom: *mod_polynom,
             polynom_shift: mod_polynom.degree() - 8,
             out_table: Self::calculate_out_table(window_size, mod_polynom),
             mod_table: Self::calculate_mod_table(mod_polynom),
@@ -138,6 +150,51 @@ impl Rabin64 {
     }
 }
 
+/// The configuration of a `Rabin64`, as it is serialized.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Rabin64Config {
+    /// The number of bits of the window size.
+    window_size_nb_bits: u32,
+
+    /// The modulo polynom.
+    mod_polynom: Polynom64,
+}
+
+#[cfg(feature = "serde")]
+impl Rabin64Config {
+    /// The maximum number of bits of the window size, bounding the size of the window and the
+    /// time spent calculating its out table when deserializing untrusted data.
+    const MAX_WINDOW_SIZE_NB_BITS: u32 = 16;
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<Rabin64Config> for Rabin64 {
+    type Error = &'static str;
+
+    fn try_from(config: Rabin64Config) -> Result<Self, Self::Error> {
+        if config.window_size_nb_bits > Rabin64Config::MAX_WINDOW_SIZE_NB_BITS
+            || config.mod_polynom.degree() < 8
+        {
+            return Err("invalid Rabin64 configuration");
+        }
+        Ok(Self::new_with_polynom(
+            config.window_size_nb_bits,
+            &config.mod_polynom,
+        ))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Rabin64> for Rabin64Config {
+    fn from(rabin: Rabin64) -> Self {
+        Self {
+            window_size_nb_bits: rabin.window_size.trailing_zeros(),
+            mod_polynom: rabin.mod_polynom,
+        }
+    }
+}
+
 impl RollingHash64 for Rabin64 {
     fn reset(&mut self) {
         self.window_data.clear();
@@ -272,4 +329,39 @@ mod tests {
             assert_eq!(rabin1.hash, rabin2.hash);
         }
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut rabin = Rabin64::new_with_polynom(5, &0x0024_5b2e_2b77_f5d7);
+        rabin.slide(42);
+        let json = serde_json::to_string(&rabin).unwrap();
+        assert_eq!(
+            json,
+            format!(
+                r#"{{"window_size_nb_bits":5,"mod_polynom":{}}}"#,
+                0x0024_5b2e_2b77_f5d7u64
+            )
+        );
+
+        let mut restored: Rabin64 = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.hash, 0);
+        let mut fresh = Rabin64::new_with_polynom(5, &0x0024_5b2e_2b77_f5d7);
+        for byte in 0..100 {
+            restored.slide(byte);
+            fresh.slide(byte);
+        }
+        assert_eq!(restored.hash, fresh.hash);
+
+        let config = |nb_bits| {
+            format!(
+                r#"{{"window_size_nb_bits":{nb_bits},"mod_polynom":{}}}"#,
+                0x0024_5b2e_2b77_f5d7u64
+            )
+        };
+        assert!(serde_json::from_str::<Rabin64>(&config(12)).is_ok());
+        assert!(serde_json::from_str::<Rabin64>(&config(17)).is_err());
+        assert!(serde_json::from_str::<Rabin64>(&config(31)).is_err());
+        assert!(serde_json::from_str::<Rabin64>(&config(40)).is_err());
+    }
 }
@@ -0,0 +1,250 @@
+use std::collections::HashSet;
+use std::fmt::{self, Debug, Formatter};
+use std::hash::Hash;
+use std::io;
+
+use crate::{Child, ChildKind, Manifest, NodeStore, PrunableChunkStore};
+
+/// A stored chunk whose data doesn't match its hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CorruptedChunk<H> {
+    /// The hash under which the chunk is stored.
+    pub hash: H,
+
+    /// The hash of the data found in the store.
+    pub actual: H,
+}
+
+/// The result of a scrub, listing the missing and corrupted objects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScrubReport<H> {
+    /// The number of stored chunks which were re-hashed.
+    pub checked_chunks: u64,
+
+    /// The number of nodes which were walked.
+    pub checked_nodes: u64,
+
+    /// The stored chunks whose data doesn't match their hash.
+    pub corrupted_chunks: Vec<CorruptedChunk<H>>,
+
+    /// The chunks referenced by a manifest or a tree which are not in the store.
+    pub missing_chunks: Vec<H>,
+
+    /// The nodes referenced by a tree which are not in the node store.
+    pub missing_nodes: Vec<H>,
+}
+
+impl<H> ScrubReport<H> {
+    /// Returns `true` if no object is missing or corrupted.
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.corrupted_chunks.is_empty()
+            && self.missing_chunks.is_empty()
+            && self.missing_nodes.is_empty()
+    }
+}
+
+/// A scrub of a chunk store, checking the integrity of its chunks and of the
+/// manifests and trees referencing them.
+pub struct Scrub<'a, S, H, F> {
+    /// The store to check.
+    store: &'a S,
+
+    /// The function calculating the hash of the data of a chunk.
+    hash: F,
+
+    /// The chunks and nodes already checked.
+    checked: HashSet<H>,
+
+    /// The report of the scrub so far.
+    report: ScrubReport<H>,
+}
+
+impl<S, H: Debug, F> Debug for Scrub<'_, S, H, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scrub")
+            .field("nb_checked", &self.checked.len())
+            .field("report", &self.report)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, S, H, F> Scrub<'a, S, H, F>
+where
+    S: PrunableChunkStore<H>,
+    H: Eq + Hash + Clone,
+    F: Fn(&[u8]) -> H,
+{
+    /// Creates a new `Scrub`.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The store to check.
+    /// * `hash` - The function calculating the hash of the data of a chunk,
+    ///   the digest the store was filled with.
+    pub fn new(store: &'a S, hash: F) -> Self {
+        Self {
+            store,
+            hash,
+            checked: HashSet::new(),
+            report: ScrubReport {
+                checked_chunks: 0,
+                checked_nodes: 0,
+                corrupted_chunks: Vec::new(),
+                missing_chunks: Vec::new(),
+                missing_nodes: Vec::new(),
+            },
+        }
+    }
+
+    /// Re-hashes every chunk of the store, and checks it against the hash it is stored under.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store can't be accessed.
+    pub fn check_chunks(&mut self) -> io::Result<()> {
+        for hash in self.store.hashes()? {
+            // A chunk listed but not found was removed during the scrub.
+            let Some(data) = self.store.get(&hash)? else {
+                continue;
+            };
+            self.report.checked_chunks += 1;
+            let actual = (self.hash)(&data);
+            if actual != hash {
+                self.report
+                    .corrupted_chunks
+                    .push(CorruptedChunk { hash, actual });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the chunks of a manifest are in the store.
+    ///
+    /// # Arguments
+    ///
+    /// * `manifest` - The manifest to check.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store can't be accessed.
+    pub fn check_manifest(&mut self, manifest: &Manifest<H>) -> io::Result<()> {
+        for entry in &manifest.entries {
+            self.check_reference(&entry.hash)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the nodes of a tree are in the node store, and its chunks in the store.
+    ///
+    /// The subtrees already checked are not walked again.
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes` - The store containing the nodes of the tree.
+    /// * `root` - The root of the tree to check.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store can't be accessed.
+    pub fn check_tree<N>(&mut self, nodes: &N, root: Child<H>) -> io::Result<()>
+    where
+        N: NodeStore<H>,
+    {
+        let mut stack = vec![root];
+        while let Some(child) = stack.pop() {
+            match child.kind {
+                ChildKind::Leaf => self.check_reference(&child.hash)?,
+                ChildKind::Node => {
+                    if !self.checked.insert(child.hash.clone()) {
+                        continue;
+                    }
+                    match nodes.get(&child.hash) {
+                        Some(node) => {
+                            self.report.checked_nodes += 1;
+                            stack.extend(node.children);
+                        }
+                        None => self.report.missing_nodes.push(child.hash),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that a referenced chunk is in the store, once.
+    fn check_reference(&mut self, hash: &H) -> io::Result<()> {
+        if self.checked.insert(hash.clone()) && !self.store.contains(hash)? {
+            self.report.missing_chunks.push(hash.clone());
+        }
+        Ok(())
+    }
+
+    /// Returns the report of the scrub.
+    pub fn finish(self) -> ScrubReport<H> {
+        self.report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn hash(data: &[u8]) -> u64 {
+        u64::from_be_bytes(test_utils::hash(data))
+    }
+
+    #[test]
+    fn scrub() {
+        let mut store = MemoryChunkStore::new();
+        let chunks: Vec<u64> = [&b"first"[..], b"second", b"third"]
+            .iter()
+            .map(|data| {
+                _ = store.put(&hash(data), data).unwrap();
+                hash(data)
+            })
+            .collect();
+        _ = store.put(&7, b"corrupted").unwrap();
+
+        let mut nodes = MemoryNodeStore::new();
+        let children = vec![
+            Child::leaf(chunks[0], 5),
+            Child::leaf(8, 4),
+            Child::node(9, 4, 2),
+        ];
+        nodes.put(Node::new(10, 2, children));
+        let manifest = Manifest::from_chunks(
+            ChunkerParams::default(),
+            [chunks[1], 8].map(|hash| {
+                let chunk = Chunk {
+                    index: 6,
+                    size: 6,
+                    separator_hash: 0,
+                };
+                (chunk, hash)
+            }),
+        );
+
+        let mut scrub = Scrub::new(&store, hash);
+        scrub.check_chunks().unwrap();
+        scrub.check_tree(&nodes, Child::node(10, 13, 4)).unwrap();
+        scrub.check_manifest(&manifest).unwrap();
+        let report = scrub.finish();
+        assert!(!report.is_clean());
+        assert_eq!(
+            report,
+            ScrubReport {
+                checked_chunks: 4,
+                checked_nodes: 1,
+                corrupted_chunks: vec![CorruptedChunk {
+                    hash: 7,
+                    actual: hash(b"corrupted"),
+                }],
+                missing_chunks: vec![8],
+                missing_nodes: vec![9],
+            }
+        );
+    }
+}
@@ -1,7 +1,8 @@
-use crate::{Rabin64, RollingHash64};
+use crate::{BoundaryPredicate, Rabin64, RollingHash64};
 
 /// A separator is a part of a stream of data that is separated by a separator.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Separator {
     /// The index of the separator in the stream.
     pub index: u64,
@@ -17,6 +18,7 @@ pub struct SeparatorIter<I, F> {
     predicate: F,
     rabin: Rabin64,
     index: u64,
+    last_separator_index: u64,
 }
 
 impl<I> SeparatorIter<I, fn(u64) -> bool>
@@ -45,7 +47,7 @@ where
 impl<I, F> SeparatorIter<I, F>
 where
     I: Iterator<Item = u8>,
-    F: Fn(u64) -> bool,
+    F: BoundaryPredicate,
 {
     /// Creates a new `SeparatorIter`.
     ///
@@ -63,14 +65,20 @@ where
             predicate,
             rabin,
             index,
+            last_separator_index: 0,
         }
     }
+
+    /// Returns a mutable reference to the iterator being separated.
+    pub(crate) fn inner_mut(&mut self) -> &mut I {
+        &mut self.iter
+    }
 }
 
 impl<I, F> Iterator for SeparatorIter<I, F>
 where
     I: Iterator<Item = u8>,
-    F: Fn(u64) -> bool,
+    F: BoundaryPredicate,
 {
     type Item = Separator;
 
@@ -79,11 +87,13 @@ where
         while let Some(byte) = self.iter.next() {
             self.rabin.slide(byte);
             self.index += 1;
-            if (self.predicate)(self.rabin.hash) {
+            let size = self.index - self.last_separator_index;
+            if self.predicate.is_boundary(self.rabin.hash, size) {
                 let separator = Separator {
                     index: self.index,
                     hash: self.rabin.hash,
                 };
+                self.last_separator_index = self.index;
 
                 // Note: We skip subsequent separators which may overlap the current one.
                 self.index += self.rabin.reset_and_prefill_window(&mut self.iter) as u64;
@@ -97,7 +107,14 @@ where
 }
 
 /// Converts a separator's hash to a level.
+///
+/// With the `serde` feature, it is serialized as its parameters.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "HashToLevelParams", into = "HashToLevelParams")
+)]
 pub struct HashToLevel {
     lvl0_nb_bits: u32,
     lvlup_nb_bits: u32,
@@ -150,6 +167,39 @@ impl HashToLevel {
     }
 }
 
+/// The parameters of a `HashToLevel`, as it is serialized.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct HashToLevelParams {
+    /// The number of bits of the level 0.
+    lvl0_nb_bits: u32,
+
+    /// The number of bits of the level up.
+    lvlup_nb_bits: u32,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<HashToLevelParams> for HashToLevel {
+    type Error = &'static str;
+
+    fn try_from(params: HashToLevelParams) -> Result<Self, Self::Error> {
+        if params.lvl0_nb_bits >= 64 || !(1..64).contains(&params.lvlup_nb_bits) {
+            return Err("invalid level parameters");
+        }
+        Ok(Self::custom_new(params.lvl0_nb_bits, params.lvlup_nb_bits))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<HashToLevel> for HashToLevelParams {
+    fn from(hash_to_level: HashToLevel) -> Self {
+        Self {
+            lvl0_nb_bits: hash_to_level.lvl0_nb_bits,
+            lvlup_nb_bits: hash_to_level.lvlup_nb_bits,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::*;
@@ -177,4 +227,24 @@ mod tests {
             assert_eq!(converter.to_level((9u64 << n) - 1), 4);
         }
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let separator = Separator {
+            index: 1234,
+            hash: 0xdead_beef,
+        };
+        let json = serde_json::to_string(&separator).unwrap();
+        assert_eq!(serde_json::from_str::<Separator>(&json).unwrap(), separator);
+
+        let converter = HashToLevel::custom_new(4, 2);
+        let json = serde_json::to_string(&converter).unwrap();
+        assert_eq!(json, r#"{"lvl0_nb_bits":4,"lvlup_nb_bits":2}"#);
+        let restored: HashToLevel = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.to_level((9u64 << 7) - 1), 1);
+
+        let invalid = r#"{"lvl0_nb_bits":4,"lvlup_nb_bits":0}"#;
+        assert!(serde_json::from_str::<HashToLevel>(invalid).is_err());
+    }
 }
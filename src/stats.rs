@@ -0,0 +1,447 @@
+use alloc::vec::Vec;
+
+use crate::{BoundaryPredicate, Chunk};
+
+/// The number of bits of a size below its highest bit distinguishing its bucket, so that the
+/// sizes of a bucket are within 1/16 of each other.
+const SUB_BUCKET_NB_BITS: u32 = 4;
+
+/// The number of sub-buckets of each power of two.
+const NB_SUB_BUCKETS: usize = 1 << SUB_BUCKET_NB_BITS;
+
+/// The number of buckets of the sizes: one for each size below `NB_SUB_BUCKETS`, then
+/// `NB_SUB_BUCKETS` for each power of two above.
+const NB_BUCKETS: usize = NB_SUB_BUCKETS * (65 - SUB_BUCKET_NB_BITS as usize);
+
+/// Returns the bucket of a size.
+#[allow(clippy::cast_possible_truncation)] // The buckets are in `0..NB_BUCKETS`.
+fn bucket(size: u64) -> usize {
+    if size < NB_SUB_BUCKETS as u64 {
+        return size as usize;
+    }
+    let shift = size.ilog2() - SUB_BUCKET_NB_BITS;
+    // The highest bit selects the power of two, the next ones the sub-bucket.
+    shift as usize * NB_SUB_BUCKETS + (size >> shift) as usize
+}
+
+/// Returns the smallest size of a bucket.
+fn bucket_min(bucket: usize) -> u64 {
+    if bucket < NB_SUB_BUCKETS {
+        return bucket as u64;
+    }
+    let shift = bucket / NB_SUB_BUCKETS - 1;
+    ((bucket % NB_SUB_BUCKETS + NB_SUB_BUCKETS) as u64) << shift
+}
+
+/// An item of a chunk iterator, from which a [`StatsIter`] gets the chunk.
+pub trait AsChunk {
+    /// Returns the chunk of the item, if any.
+    fn as_chunk(&self) -> Option<&Chunk>;
+}
+
+impl AsChunk for Chunk {
+    fn as_chunk(&self) -> Option<&Chunk> {
+        Some(self)
+    }
+}
+
+/// A chunk with its data, or its hash.
+impl<T> AsChunk for (Chunk, T) {
+    fn as_chunk(&self) -> Option<&Chunk> {
+        Some(&self.0)
+    }
+}
+
+/// A chunk, or an error which is not counted.
+impl<T: AsChunk, E> AsChunk for Result<T, E> {
+    fn as_chunk(&self) -> Option<&Chunk> {
+        self.as_ref().ok().and_then(AsChunk::as_chunk)
+    }
+}
+
+/// A collector of statistics about chunks, to monitor the health of a chunking.
+///
+/// A chunk whose separator hash doesn't satisfy the boundary predicate was not cut at a
+/// content-defined boundary, it is a forced cut. The last chunk of a stream is cut by the end of
+/// the stream instead, call [`ChunkStats::end_stream`] after it, or wrap the chunk iterator with
+/// [`ChunkStats::inspect`] which does it.
+///
+/// The memory used doesn't depend on the number of chunks: the sizes are counted in buckets,
+/// and their percentiles are approximated by the smallest size of their bucket, which is exact
+/// below 32 and less than 1/16 below the actual size above.
+#[derive(Debug, Clone)]
+pub struct ChunkStats<P> {
+    /// The predicate used to determine if a separator is a separator boundary.
+    predicate: P,
+
+    /// The number of chunks.
+    nb_chunks: usize,
+
+    /// The size of the smallest chunk.
+    min: u64,
+
+    /// The size of the largest chunk.
+    max: u64,
+
+    /// The number of chunks of each bucket of sizes, see [`bucket`].
+    buckets: [u64; NB_BUCKETS],
+
+    /// The total size of the chunks.
+    total_bytes: u64,
+
+    /// The mean of the sizes, updated with Welford's algorithm.
+    mean: f64,
+
+    /// The sum of the squared differences to the mean of the sizes.
+    m2: f64,
+
+    /// The number of chunks cut at a content-defined boundary.
+    boundaries: u64,
+
+    /// The number of chunks cut elsewhere, excluding the ends of streams.
+    forced_cuts: u64,
+
+    /// The number of streams ended.
+    streams: u64,
+
+    /// `true` if the last chunk added is a forced cut.
+    last_forced: bool,
+}
+
+/// A summary of the statistics of chunks.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChunkStatsSummary {
+    /// The number of chunks.
+    pub nb_chunks: u64,
+
+    /// The number of streams ended.
+    pub nb_streams: u64,
+
+    /// The total size of the chunks.
+    pub total_bytes: u64,
+
+    /// The size of the smallest chunk.
+    pub min: u64,
+
+    /// The size of the largest chunk.
+    pub max: u64,
+
+    /// The mean size of the chunks.
+    pub mean: f64,
+
+    /// The variance of the sizes of the chunks, against their mean.
+    pub variance: f64,
+
+    /// The median size of the chunks, approximated as described in [`ChunkStats`].
+    pub p50: u64,
+
+    /// The 90th percentile of the sizes of the chunks, approximated.
+    pub p90: u64,
+
+    /// The 99th percentile of the sizes of the chunks, approximated.
+    pub p99: u64,
+
+    /// The number of chunks for each non-empty size range `2^k..2^(k+1)`, as `(2^k, count)`.
+    pub histogram: Vec<(u64, u64)>,
+
+    /// The number of content-defined boundaries per byte.
+    pub boundary_rate: f64,
+
+    /// The ratio of chunks which were forced cuts.
+    pub forced_cut_ratio: f64,
+}
+
+impl ChunkStatsSummary {
+    /// Returns the standard deviation of the sizes of the chunks.
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn std_dev(&self) -> f64 {
+        self.variance.sqrt()
+    }
+}
+
+impl<P: BoundaryPredicate> ChunkStats<P> {
+    /// Creates a new, empty `ChunkStats`.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - The predicate used to determine if a separator is a separator boundary,
+    ///   e.g. [`ChunkerParams::predicate`](crate::ChunkerParams::predicate).
+    pub fn new(predicate: P) -> Self {
+        Self {
+            predicate,
+            nb_chunks: 0,
+            min: u64::MAX,
+            max: 0,
+            buckets: [0; NB_BUCKETS],
+            total_bytes: 0,
+            mean: 0.0,
+            m2: 0.0,
+            boundaries: 0,
+            forced_cuts: 0,
+            streams: 0,
+            last_forced: false,
+        }
+    }
+
+    /// Adds a chunk.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk` - The chunk to add.
+    #[allow(clippy::cast_precision_loss)] // The statistics don't need to be exact.
+    pub fn add(&mut self, chunk: &Chunk) {
+        self.nb_chunks += 1;
+        self.min = self.min.min(chunk.size);
+        self.max = self.max.max(chunk.size);
+        self.buckets[bucket(chunk.size)] += 1;
+        self.total_bytes += chunk.size;
+
+        let delta = chunk.size as f64 - self.mean;
+        self.mean += delta / self.nb_chunks as f64;
+        self.m2 += delta * (chunk.size as f64 - self.mean);
+
+        self.last_forced = !self.predicate.is_boundary(chunk.separator_hash, chunk.size);
+        if self.last_forced {
+            self.forced_cuts += 1;
+        } else {
+            self.boundaries += 1;
+        }
+    }
+
+    /// Ends a stream: the last chunk added was cut by the end of the stream, not forced.
+    pub fn end_stream(&mut self) {
+        if self.last_forced {
+            self.forced_cuts -= 1;
+            self.last_forced = false;
+        }
+        self.streams += 1;
+    }
+
+    /// Wraps a chunk iterator, adding its chunks as they are iterated, and ending the stream
+    /// when it is exhausted.
+    ///
+    /// # Arguments
+    ///
+    /// * `iter` - The chunks of a stream, possibly with their data as yielded by
+    ///   [`ChunkDataIter`](crate::ChunkDataIter), see [`AsChunk`].
+    pub fn inspect<I>(&mut self, iter: I) -> StatsIter<'_, I, P>
+    where
+        I: Iterator,
+        I::Item: AsChunk,
+    {
+        StatsIter {
+            stats: self,
+            iter,
+            ended: false,
+        }
+    }
+
+    /// Returns the number of chunks added.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.nb_chunks
+    }
+
+    /// Returns `true` if no chunk was added.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.nb_chunks == 0
+    }
+
+    /// Returns the summary of the statistics.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)] // The statistics don't need to be exact.
+    pub fn summary(&self) -> ChunkStatsSummary {
+        let nb_chunks = self.nb_chunks as u64;
+        // The buckets don't overlap the powers of two.
+        let mut histogram: Vec<(u64, u64)> = Vec::new();
+        for (bucket, count) in self.buckets.iter().enumerate().skip(1) {
+            let min = 1 << bucket_min(bucket).ilog2();
+            match histogram.last_mut() {
+                Some((last_min, last_count)) if *last_min == min => *last_count += count,
+                _ if *count > 0 => histogram.push((min, *count)),
+                _ => {}
+            }
+        }
+        let ratio = |count: u64, total: u64| {
+            if total == 0 {
+                0.0
+            } else {
+                count as f64 / total as f64
+            }
+        };
+
+        ChunkStatsSummary {
+            nb_chunks,
+            nb_streams: self.streams,
+            total_bytes: self.total_bytes,
+            min: self.min.min(self.max),
+            max: self.max,
+            mean: self.mean,
+            variance: if nb_chunks == 0 {
+                0.0
+            } else {
+                self.m2 / nb_chunks as f64
+            },
+            p50: self.percentile(50),
+            p90: self.percentile(90),
+            p99: self.percentile(99),
+            histogram,
+            boundary_rate: ratio(self.boundaries, self.total_bytes),
+            forced_cut_ratio: ratio(self.forced_cuts, nb_chunks),
+        }
+    }
+
+    /// Returns a percentile of the sizes with the nearest-rank method, approximated by the
+    /// smallest size of its bucket.
+    fn percentile(&self, percent: u64) -> u64 {
+        if self.nb_chunks == 0 {
+            return 0;
+        }
+        let nb_chunks = self.nb_chunks as u64;
+        let rank = (percent * nb_chunks).div_ceil(100).clamp(1, nb_chunks);
+        let mut count = 0;
+        for (bucket, bucket_count) in self.buckets.iter().enumerate() {
+            count += bucket_count;
+            if count >= rank {
+                return bucket_min(bucket).clamp(self.min, self.max);
+            }
+        }
+        self.max
+    }
+}
+
+/// An iterator adding the chunks it yields to a [`ChunkStats`], see [`ChunkStats::inspect`].
+#[derive(Debug)]
+pub struct StatsIter<'a, I, P> {
+    /// The statistics to add the chunks to.
+    stats: &'a mut ChunkStats<P>,
+
+    /// The chunks of the stream.
+    iter: I,
+
+    /// `true` if the stream was ended.
+    ended: bool,
+}
+
+impl<I, P> Iterator for StatsIter<'_, I, P>
+where
+    I: Iterator,
+    I::Item: AsChunk,
+    P: BoundaryPredicate,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.iter.next();
+        match &item {
+            Some(item) => {
+                if let Some(chunk) = item.as_chunk() {
+                    self.stats.add(chunk);
+                }
+            }
+            None if !self.ended => {
+                self.stats.end_stream();
+                self.ended = true;
+            }
+            None => {}
+        }
+        item
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::stats::{bucket, bucket_min, NB_BUCKETS};
+    use crate::test_utils::random_data;
+    use crate::*;
+
+    #[test]
+    fn buckets() {
+        for size in (0..100_000).chain([u64::MAX / 3, u64::MAX - 1, u64::MAX]) {
+            let size_bucket = bucket(size);
+            assert!(size_bucket < NB_BUCKETS);
+            let min = bucket_min(size_bucket);
+            assert!(min <= size && size - min <= size / 16, "{size}");
+            assert_eq!(bucket(min), size_bucket);
+        }
+        assert_eq!(bucket(u64::MAX), NB_BUCKETS - 1);
+    }
+
+    #[test]
+    fn approximate_percentiles() {
+        let mut stats = ChunkStats::new(|_| true);
+        for size in 1..=100_000 {
+            stats.add(&Chunk {
+                index: 0,
+                size,
+                separator_hash: 0,
+            });
+        }
+
+        let summary = stats.summary();
+        assert_eq!((summary.min, summary.max), (1, 100_000));
+        for (percentile, exact) in [(summary.p50, 50_000), (summary.p90, 90_000)] {
+            assert!(percentile <= exact && exact - percentile <= exact / 16);
+        }
+        assert_eq!(summary.histogram[16], (65_536, 100_000 - 65_535));
+    }
+
+    #[test]
+    fn chunks_with_data() {
+        let params = ChunkerParams {
+            separator_size_nb_bits: 4,
+            boundary_nb_bits: 8,
+            ..ChunkerParams::default()
+        };
+        let data = random_data(50_000, 7);
+        let mut stats = ChunkStats::new(params.predicate());
+        let nb_chunks = stats.inspect(params.chunk_data(&data[..])).count();
+
+        let summary = stats.summary();
+        assert_eq!(summary.nb_chunks, nb_chunks as u64);
+        assert_eq!(summary.total_bytes, data.len() as u64);
+        assert_eq!(summary.nb_streams, 1);
+        assert!(summary.forced_cut_ratio.abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn stats() {
+        let chunk = |size, separator_hash| Chunk {
+            index: 0,
+            size,
+            separator_hash,
+        };
+        let mut stats = ChunkStats::new(|hash| hash & 0xf == 0xf);
+        let chunks = [chunk(4, 0xf), chunk(8, 0x1f), chunk(16, 0x3), chunk(12, 0)];
+        assert_eq!(stats.inspect(chunks.into_iter()).count(), 4);
+        stats.add(&chunk(10, 0xf));
+        stats.end_stream();
+
+        let summary = stats.summary();
+        assert!((summary.std_dev() - 4.0).abs() < 1e-9);
+        assert_eq!(
+            ChunkStatsSummary {
+                variance: summary.variance.round(),
+                ..summary
+            },
+            ChunkStatsSummary {
+                nb_chunks: 5,
+                nb_streams: 2,
+                total_bytes: 50,
+                min: 4,
+                max: 16,
+                mean: 10.0,
+                variance: 16.0,
+                p50: 10,
+                p90: 16,
+                p99: 16,
+                histogram: vec![(4, 1), (8, 3), (16, 1)],
+                boundary_rate: 3.0 / 50.0,
+                forced_cut_ratio: 1.0 / 5.0,
+            }
+        );
+    }
+}
@@ -0,0 +1,386 @@
+//! A have/want protocol synchronizing a file between two chunk stores, transferring only the
+//! chunks missing on the receiver.
+//!
+//! # Protocol
+//!
+//! The messages are encoded with the same conventions as the [`codec`](crate::codec) module,
+//! all integers written as `varint` using the unsigned LEB128 encoding:
+//!
+//! 1. The sender sends the hashes of the chunks of the file:
+//!
+//!    ```text
+//!    [u8; 4]  magic "CDCS"
+//!    u8       format version (currently 1)
+//!    u8       hash length `n`
+//!    varint   number of chunks
+//!    [u8; n]  for each chunk, in stream order, its hash
+//!    ```
+//!
+//! 2. The receiver answers with the chunks it wants, those missing in its store, each once:
+//!
+//!    ```text
+//!    varint   number of wanted chunks
+//!    varint   for each wanted chunk, its position in the list of hashes
+//!    ```
+//!
+//! 3. The sender sends the data of the wanted chunks, in the order they were wanted:
+//!
+//!    ```text
+//!    varint   length
+//!    [u8]     data
+//!    ```
+//!
+//! The receiver verifies the data against the hashes, puts the chunks in its store, and
+//! restores the file from it.
+
+use std::collections::HashSet;
+use std::fmt::{self, Debug, Formatter};
+use std::hash::Hash;
+use std::io::{self, Read, Write};
+
+use crate::codec::{common_hash_len, read_varint, write_varint};
+use crate::{ChunkReader, ChunkStore, DecodeError, RestoreError};
+
+/// The magic bytes at the start of a synchronization.
+pub const SYNC_MAGIC: [u8; 4] = *b"CDCS";
+
+/// The current version of the synchronization format, bumped on any change of the format.
+pub const SYNC_VERSION: u8 = 1;
+
+/// An error in a message of a synchronization, the other side not following the protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum SyncError {
+    /// A message couldn't be decoded.
+    Decode(DecodeError),
+
+    /// A wanted chunk is at a position out of the list of hashes.
+    InvalidPosition(u64),
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decode(err) => write!(f, "invalid message: {err}"),
+            Self::InvalidPosition(position) => write!(f, "invalid wanted position {position}"),
+        }
+    }
+}
+
+impl std::error::Error for SyncError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Decode(err) => Some(err),
+            Self::InvalidPosition(_) => None,
+        }
+    }
+}
+
+impl From<DecodeError> for SyncError {
+    fn from(err: DecodeError) -> Self {
+        Self::Decode(err)
+    }
+}
+
+/// Statistics about a synchronization.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SyncStats {
+    /// The number of chunks of the file.
+    pub nb_chunks: u64,
+
+    /// The number of chunks transferred.
+    pub transferred_chunks: u64,
+
+    /// The total size of the chunks transferred.
+    pub transferred_bytes: u64,
+}
+
+/// Sends a file to a receiver, see [`receive`].
+///
+/// # Arguments
+///
+/// * `store` - The store containing the chunks of the file.
+/// * `hashes` - The hashes of the chunks of the file, in stream order.
+/// * `stream` - The connection to the receiver.
+///
+/// # Errors
+///
+/// Returns an error if the connection or the store fails, an [`io::ErrorKind::NotFound`] error
+/// if a wanted chunk is not in the store, or an [`io::ErrorKind::InvalidData`] error wrapping
+/// a [`SyncError`] if the receiver doesn't follow the protocol.
+///
+/// # Panics
+///
+/// Panics if the hashes don't all have the same length, or if it is longer than 255 bytes.
+pub fn send<S, H, T>(store: &S, hashes: &[H], mut stream: T) -> io::Result<SyncStats>
+where
+    S: ChunkStore<H>,
+    H: AsRef<[u8]> + Clone + Debug + Send + Sync + 'static,
+    T: Read + Write,
+{
+    let mut have = Vec::new();
+    have.extend_from_slice(&SYNC_MAGIC);
+    have.push(SYNC_VERSION);
+    have.push(common_hash_len(hashes));
+    write_varint(&mut have, hashes.len() as u64);
+    for hash in hashes {
+        have.extend_from_slice(hash.as_ref());
+    }
+    stream.write_all(&have)?;
+    stream.flush()?;
+
+    let mut stats = SyncStats {
+        nb_chunks: hashes.len() as u64,
+        ..SyncStats::default()
+    };
+    let nb_wanted = read_varint(&mut stream)?;
+    let mut wanted = Vec::new();
+    for _ in 0..nb_wanted {
+        let position = read_varint(&mut stream)?;
+        let hash = usize::try_from(position)
+            .ok()
+            .and_then(|position| hashes.get(position))
+            .ok_or_else(|| invalid(SyncError::InvalidPosition(position)))?;
+        wanted.push(hash);
+    }
+
+    for hash in wanted {
+        let Some(data) = store.get(hash)? else {
+            let err = RestoreError::MissingChunk(hash.clone());
+            return Err(io::Error::new(io::ErrorKind::NotFound, err));
+        };
+        let mut buf = Vec::with_capacity(data.len() + 10);
+        write_varint(&mut buf, data.len() as u64);
+        buf.extend_from_slice(&data);
+        stream.write_all(&buf)?;
+        stats.transferred_chunks += 1;
+        stats.transferred_bytes += data.len() as u64;
+    }
+    stream.flush()?;
+
+    Ok(stats)
+}
+
+/// Receives a file from a sender, see [`send`], and writes it.
+///
+/// # Arguments
+///
+/// * `store` - The store to put the missing chunks in.
+/// * `hash` - The function calculating the hash of the data of a chunk.
+/// * `stream` - The connection to the sender.
+/// * `out` - The writer of the file.
+///
+/// # Errors
+///
+/// Returns an error if the connection, the store or the writer fails, an
+/// [`io::ErrorKind::InvalidData`] error wrapping a [`RestoreError`] if a chunk doesn't match
+/// its hash, or wrapping a [`SyncError`] if the sender doesn't follow the protocol.
+pub fn receive<S, H, F, T, W>(
+    store: &mut S,
+    hash: F,
+    mut stream: T,
+    mut out: W,
+) -> io::Result<SyncStats>
+where
+    S: ChunkStore<H>,
+    H: for<'a> TryFrom<&'a [u8]> + Eq + Hash + Clone + Debug + Send + Sync + 'static,
+    F: Fn(&[u8]) -> H,
+    T: Read + Write,
+    W: Write,
+{
+    let mut header = [0; 6];
+    stream.read_exact(&mut header)?;
+    if header[..4] != SYNC_MAGIC {
+        return Err(invalid(DecodeError::InvalidMagic.into()));
+    }
+    if header[4] != SYNC_VERSION {
+        return Err(invalid(DecodeError::UnsupportedVersion(header[4]).into()));
+    }
+    let mut hash_bytes = vec![0; usize::from(header[5])];
+
+    let nb_chunks = read_varint(&mut stream)?;
+    let mut hashes = Vec::new();
+    let mut wanted = Vec::new();
+    let mut wanted_hashes = HashSet::new();
+    for position in 0..nb_chunks {
+        stream.read_exact(&mut hash_bytes)?;
+        let chunk_hash =
+            H::try_from(&hash_bytes).map_err(|_| invalid(DecodeError::InvalidHash.into()))?;
+        if !wanted_hashes.contains(&chunk_hash) && !store.contains(&chunk_hash)? {
+            _ = wanted_hashes.insert(chunk_hash.clone());
+            wanted.push((position, chunk_hash.clone()));
+        }
+        hashes.push(chunk_hash);
+    }
+
+    let mut want = Vec::new();
+    write_varint(&mut want, wanted.len() as u64);
+    for (position, _) in &wanted {
+        write_varint(&mut want, *position);
+    }
+    stream.write_all(&want)?;
+    stream.flush()?;
+
+    let mut stats = SyncStats {
+        nb_chunks,
+        ..SyncStats::default()
+    };
+    for (_, expected) in wanted {
+        let len = read_varint(&mut stream)?;
+        let mut data = Vec::new();
+        if (&mut stream).take(len).read_to_end(&mut data)? as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let actual = hash(&data);
+        if actual != expected {
+            let err = RestoreError::HashMismatch { expected, actual };
+            return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+        }
+        _ = store.put(&expected, &data)?;
+        stats.transferred_chunks += 1;
+        stats.transferred_bytes += len;
+    }
+
+    _ = io::copy(&mut ChunkReader::new(&*store, hashes, hash), &mut out)?;
+    out.flush()?;
+    Ok(stats)
+}
+
+/// Returns an [`io::ErrorKind::InvalidData`] error wrapping a [`SyncError`].
+fn invalid(err: SyncError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Write};
+    use std::sync::mpsc::{channel, Receiver, Sender};
+    use std::thread;
+
+    use crate::test_utils::{hash, pseudo_random};
+    use crate::*;
+
+    /// A chunk store of the tests.
+    type Store = MemoryChunkStore<[u8; 8]>;
+
+    /// One end of an in-memory connection.
+    struct Duplex {
+        tx: Sender<Vec<u8>>,
+        rx: Receiver<Vec<u8>>,
+        buf: io::Cursor<Vec<u8>>,
+    }
+
+    /// Returns both ends of an in-memory connection.
+    fn duplex() -> (Duplex, Duplex) {
+        let (tx_a, rx_a) = channel();
+        let (tx_b, rx_b) = channel();
+        let end = |tx, rx| Duplex {
+            tx,
+            rx,
+            buf: io::Cursor::default(),
+        };
+        (end(tx_a, rx_b), end(tx_b, rx_a))
+    }
+
+    impl Read for Duplex {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.buf.position() == self.buf.get_ref().len() as u64 {
+                // The other end was dropped at the end of the data.
+                let Ok(data) = self.rx.recv() else {
+                    return Ok(0);
+                };
+                self.buf = io::Cursor::new(data);
+            }
+            self.buf.read(buf)
+        }
+    }
+
+    impl Write for Duplex {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.tx
+                .send(buf.to_vec())
+                .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Returns an old and a new version of a file, with the stores of a sender having the new
+    /// version and of a receiver having the old one, and the hashes of the new version.
+    fn stores() -> (Vec<u8>, Store, Store, Vec<[u8; 8]>) {
+        let old = pseudo_random(40_000);
+        let mut new = old.clone();
+        new[20_000..20_010].copy_from_slice(b"0123456789");
+
+        let params = ChunkerParams {
+            separator_size_nb_bits: 4,
+            boundary_nb_bits: 8,
+            ..ChunkerParams::default()
+        };
+        let mut sender_store = MemoryChunkStore::new();
+        let (chunks, _) =
+            store_chunks(&mut sender_store, params.chunk_data(&new[..]), hash).unwrap();
+        let hashes = chunks.iter().map(|(_, hash)| *hash).collect();
+        let mut receiver_store = MemoryChunkStore::new();
+        _ = store_chunks(&mut receiver_store, params.chunk_data(&old[..]), hash).unwrap();
+        (new, sender_store, receiver_store, hashes)
+    }
+
+    /// Synchronizes the new version over a pair of connected streams, and checks the result.
+    fn check_sync<T>(sender_stream: T, receiver_stream: T)
+    where
+        T: Read + Write + Send + 'static,
+    {
+        let (new, sender_store, mut receiver_store, hashes) = stores();
+        let nb_chunks = hashes.len() as u64;
+        let sender =
+            thread::spawn(move || sync::send(&sender_store, &hashes, sender_stream).unwrap());
+        let mut received = vec![];
+        let stats =
+            sync::receive(&mut receiver_store, hash, receiver_stream, &mut received).unwrap();
+        assert_eq!(sender.join().unwrap(), stats);
+
+        assert_eq!(received, new);
+        assert_eq!(stats.nb_chunks, nb_chunks);
+        assert!(stats.transferred_chunks > 0);
+        assert!(stats.transferred_bytes < 2_000);
+    }
+
+    #[test]
+    fn sync_in_memory() {
+        let (sender_stream, receiver_stream) = duplex();
+        check_sync(sender_stream, receiver_stream);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sync_over_unix_socket() {
+        let (sender_stream, receiver_stream) = std::os::unix::net::UnixStream::pair().unwrap();
+        check_sync(sender_stream, receiver_stream);
+    }
+
+    #[test]
+    fn invalid_messages() {
+        let (_, sender_store, _, hashes) = stores();
+        let (sender_stream, mut receiver_stream) = duplex();
+        // One wanted chunk, at the position 1000, after the last one.
+        receiver_stream.write_all(&[1, 0xe8, 0x07]).unwrap();
+        let err = sync::send(&sender_store, &hashes, sender_stream).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = err.into_inner().unwrap().downcast::<SyncError>().unwrap();
+        assert_eq!(*err, SyncError::InvalidPosition(1000));
+
+        let (mut sender_stream, receiver_stream) = duplex();
+        sender_stream.write_all(b"CDCS\x02\x08\x00").unwrap();
+        drop(sender_stream);
+        let mut store = MemoryChunkStore::new();
+        let err = sync::receive(&mut store, hash, receiver_stream, vec![]).unwrap_err();
+        let err = err.into_inner().unwrap().downcast::<SyncError>().unwrap();
+        assert_eq!(*err, SyncError::Decode(DecodeError::UnsupportedVersion(2)));
+    }
+}
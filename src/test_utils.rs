@@ -0,0 +1,90 @@
+//! Helpers shared by the tests of the crate.
+
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+use crate::{Child, HashedChunk, Node, NodeIter};
+
+#[cfg(feature = "std")]
+/// The levels of the chunks of the test tree.
+pub(crate) const TREE_LEVELS: [usize; 12] = [0, 0, 1, 0, 1, 1, 2, 1, 0, 1, 0, 1];
+
+#[cfg(feature = "std")]
+/// Returns the chunks of the test tree, hashed with their index.
+///
+/// # Arguments
+///
+/// * `size` - The size of a chunk from its index.
+pub(crate) fn tree_chunks(size: impl Fn(u64) -> u64) -> Vec<HashedChunk<u64>> {
+    (0..)
+        .zip(TREE_LEVELS)
+        .map(|(index, level)| HashedChunk {
+            hash: index,
+            level,
+            size: size(index),
+        })
+        .collect()
+}
+
+#[cfg(feature = "std")]
+/// Creates a node whose hash is derived from its level and its children.
+// The signature is the one expected by `NodeIter`.
+#[allow(clippy::ptr_arg)]
+pub(crate) fn new_node(level: usize, children: &Vec<Child<u64>>) -> Node<u64> {
+    let hash = children.iter().fold(1_000 + level as u64, |hash, child| {
+        hash.wrapping_mul(31) ^ child.hash
+    });
+    Node::new(hash, level, children.clone())
+}
+
+#[cfg(feature = "std")]
+/// Returns the nodes built from chunks with [`new_node`], and the root of their tree.
+pub(crate) fn tree(chunks: &[HashedChunk<u64>]) -> (Vec<Node<u64>>, Child<u64>) {
+    let mut node_iter = NodeIter::new(chunks.iter().copied(), new_node, 0);
+    let nodes = node_iter.by_ref().collect();
+    (nodes, node_iter.root().unwrap())
+}
+
+#[cfg(feature = "std")]
+/// Returns the FNV-1a hash of some data, a fast hash for the tests of the chunk stores.
+pub(crate) fn hash(data: &[u8]) -> [u8; 8] {
+    let hash = data.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    hash.to_be_bytes()
+}
+
+#[cfg(feature = "std")]
+/// Returns data repeating every 251 bytes, the squares of the indexes modulo 251.
+pub(crate) fn periodic_data(len: u32) -> Vec<u8> {
+    (0..len).map(|i| (i * i % 251).to_le_bytes()[0]).collect()
+}
+
+/// Returns pseudo-random data from a multiplicative hash of the indexes, without long
+/// repetitions.
+pub(crate) fn pseudo_random(len: u32) -> Vec<u8> {
+    (0..len)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 13).to_le_bytes()[0])
+        .collect()
+}
+
+/// The default seed of the pseudo-random numbers.
+pub(crate) const SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+/// Returns an endless xorshift sequence of pseudo-random numbers.
+pub(crate) fn xorshift(mut state: u64) -> impl Iterator<Item = u64> {
+    core::iter::repeat_with(move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    })
+}
+
+/// Returns pseudo-random data of good quality, the lowest bytes of a [`xorshift`] sequence.
+pub(crate) fn random_data(len: usize, seed: u64) -> Vec<u8> {
+    xorshift(seed)
+        .take(len)
+        .map(|state| state.to_le_bytes()[0])
+        .collect()
+}
@@ -1,17 +1,91 @@
+use alloc::{vec, vec::Vec};
+
 /// Example of type to use with the generic structures below.
 //pub type Hash256 = [u8; 256/8];
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HashedChunk<H> {
     /// The hash of the chunk.
     pub hash: H,
 
     /// The level of the chunk.
     pub level: usize,
+
+    /// The size of the chunk.
+    pub size: u64,
+}
+
+/// The kind of a child of a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChildKind {
+    /// The child is a chunk.
+    Leaf,
+
+    /// The child is another node.
+    Node,
+}
+
+/// A child of a node, which is either a chunk or another node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Child<H> {
+    /// The hash of the chunk or of the node.
+    pub hash: H,
+
+    /// Whether the child is a chunk or a node.
+    pub kind: ChildKind,
+
+    /// The total size of the chunks below the child.
+    pub size: u64,
+
+    /// The number of chunks below the child, 1 for a chunk.
+    pub nb_chunks: u64,
+}
+
+impl<H> Child<H> {
+    /// Creates a new `Child` referring to a chunk.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the chunk.
+    /// * `size` - The size of the chunk.
+    pub fn leaf(hash: H, size: u64) -> Self {
+        Self {
+            hash,
+            kind: ChildKind::Leaf,
+            size,
+            nb_chunks: 1,
+        }
+    }
+
+    /// Creates a new `Child` referring to a node.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the node.
+    /// * `size` - The total size of the chunks below the node.
+    /// * `nb_chunks` - The number of chunks below the node.
+    pub fn node(hash: H, size: u64, nb_chunks: u64) -> Self {
+        Self {
+            hash,
+            kind: ChildKind::Node,
+            size,
+            nb_chunks,
+        }
+    }
+
+    /// Returns `true` if the child is a chunk.
+    #[must_use]
+    pub fn is_leaf(&self) -> bool {
+        self.kind == ChildKind::Leaf
+    }
 }
 
 /// A node in a tree.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node<H> {
     /// The hash of the node.
     ///
@@ -23,8 +97,41 @@ pub struct Node<H> {
     /// The level of the node.
     pub level: usize,
 
+    /// The total size of the chunks below the node.
+    pub size: u64,
+
+    /// The number of chunks below the node.
+    pub nb_chunks: u64,
+
     /// The children of the node.
-    pub children: Vec<H>,
+    pub children: Vec<Child<H>>,
+}
+
+impl<H> Node<H> {
+    /// Creates a new `Node`, computing its size and number of chunks from its children.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the node.
+    /// * `level` - The level of the node.
+    /// * `children` - The children of the node.
+    pub fn new(hash: H, level: usize, children: Vec<Child<H>>) -> Self {
+        Self {
+            hash,
+            level,
+            size: children.iter().map(|child| child.size).sum(),
+            nb_chunks: children.iter().map(|child| child.nb_chunks).sum(),
+            children,
+        }
+    }
+
+    /// Returns this node as the child of another node.
+    pub fn as_child(&self) -> Child<H>
+    where
+        H: Copy,
+    {
+        Child::node(self.hash, self.size, self.nb_chunks)
+    }
 }
 
 /// An iterator that generates nodes from hashed chunks.
@@ -39,20 +146,32 @@ pub struct NodeIter<I, F, H> {
     /// The maximum number of children a node can have.
     max_children: usize,
 
-    /// The hashes at each level.
-    level_hashes: Vec<Vec<H>>, // level_hashes[level] -> Vec<H>
+    /// The children at each level.
+    level_hashes: Vec<Vec<Child<H>>>, // level_hashes[level] -> Vec<Child<H>>
 
     /// The output buffer.
     out_buffer: Vec<Node<H>>, // Fifo
+
+    /// The root of the tree, once all the chunks were consumed.
+    root: Option<Child<H>>,
 }
 
 impl<I, F, H> NodeIter<I, F, H>
 where
     I: Iterator<Item = HashedChunk<H>>,
-    F: Fn(usize, &Vec<H>) -> Node<H>,
+    F: Fn(usize, &Vec<Child<H>>) -> Node<H>,
     H: Copy,
 {
     /// Creates a new `NodeIter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `iter` - The hashed chunks to generate nodes from.
+    /// * `new_node` - The function to create a new node from its level and children,
+    ///   usually calculating its hash and calling [`Node::new`]. Only the hash of the returned
+    ///   node is used: the emitted node has these children, and its size and number of chunks
+    ///   are calculated from them.
+    /// * `max_node_children` - The maximum number of children a node can have, 0 for no limit.
     pub fn new(iter: I, new_node: F, max_node_children: usize) -> Self {
         Self {
             chunks: iter,
@@ -60,22 +179,33 @@ where
             max_children: max_node_children,
             level_hashes: Vec::with_capacity(16),
             out_buffer: Vec::with_capacity(16),
+            root: None,
         }
     }
 
-    /// Adds a hash at a specific level.
+    /// Returns the root of the tree.
+    ///
+    /// The root is only known once the iterator is exhausted, before that `None` is returned.
+    /// It is a [`ChildKind::Node`] referring to the last emitted node, or a [`ChildKind::Leaf`]
+    /// if the stream has a single chunk, in which case no node is emitted at all.
+    /// It is also `None` for an empty stream.
+    pub fn root(&self) -> Option<Child<H>> {
+        self.root
+    }
+
+    /// Adds a child at a specific level.
     ///
     /// # Arguments
     ///
-    /// * `level` - The level to add the hash at.
-    /// * `hash` - The hash to add.
-    fn add_at_level(&mut self, level: usize, hash: H) {
+    /// * `level` - The level to add the child at.
+    /// * `child` - The child to add.
+    fn add_at_level(&mut self, level: usize, child: Child<H>) {
         // Ensures that the vector is large enough.
         if level >= self.level_hashes.len() {
             self.level_hashes.resize(level + 1, vec![]);
         }
 
-        self.level_hashes[level].push(hash);
+        self.level_hashes[level].push(child);
 
         // If max_children was set to non-zero, limit the number of children.
         if self.level_hashes[level].len() == self.max_children {
@@ -98,10 +228,12 @@ where
                 self.add_at_level(level + 1, level_up_hash);
             }
             _ => {
-                let node = (self.new_node)(level, &self.level_hashes[level]);
-                let level_up_hash = node.hash;
+                // Only the hash is taken from the closure, the totals are the children's.
+                let hash = (self.new_node)(level, &self.level_hashes[level]).hash;
+                let children = core::mem::take(&mut self.level_hashes[level]);
+                let node = Node::new(hash, level, children);
+                let level_up_hash = node.as_child();
                 self.out_buffer.push(node);
-                self.level_hashes[level].clear();
                 self.add_at_level(level + 1, level_up_hash);
             }
         }
@@ -122,7 +254,7 @@ where
 impl<I, F, H> Iterator for NodeIter<I, F, H>
 where
     I: Iterator<Item = HashedChunk<H>>,
-    F: Fn(usize, &Vec<H>) -> Node<H>,
+    F: Fn(usize, &Vec<Child<H>>) -> Node<H>,
     H: Copy,
 {
     type Item = Node<H>;
@@ -134,7 +266,7 @@ where
             }
 
             if let Some(chunk) = self.chunks.next() {
-                self.add_at_level(0, chunk.hash);
+                self.add_at_level(0, Child::leaf(chunk.hash, chunk.size));
                 self.output_levels(chunk.level);
                 self.out_buffer.reverse();
             } else {
@@ -142,6 +274,8 @@ where
                 if len > 0 {
                     // Flush the remaining hashes.
                     self.output_levels(len);
+                    // The remaining child was moved up to the top level, it is the root.
+                    self.root = self.level_hashes.iter().flatten().next().copied();
                     self.level_hashes.clear();
                     self.out_buffer.reverse();
                 } else {
@@ -151,3 +285,36 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn node_totals_from_children() {
+        let chunks = (0..4u64).map(|index| HashedChunk {
+            hash: index,
+            level: usize::from(index == 1),
+            size: 10,
+        });
+        // A node built by hand with wrong totals and children.
+        let new_node = |level, children: &Vec<Child<u64>>| Node {
+            hash: 100 + children[0].hash,
+            level,
+            size: 1,
+            nb_chunks: 42,
+            children: vec![],
+        };
+        let mut node_iter = NodeIter::new(chunks, new_node, 0);
+        let nodes: Vec<Node<u64>> = node_iter.by_ref().collect();
+
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(
+            nodes[0],
+            Node::new(100, 0, vec![Child::leaf(0, 10), Child::leaf(1, 10)])
+        );
+        assert_eq!(nodes[2].size, 40);
+        assert_eq!(nodes[2].nb_chunks, 4);
+        assert_eq!(node_iter.root(), Some(nodes[2].as_child()));
+    }
+}
@@ -0,0 +1,266 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use crate::{BoundaryPredicate, Rabin64, RollingHash64, Separator};
+
+/// An iterator that separates data with the Two Thresholds, Two Divisors (TTTD) algorithm.
+///
+/// Like [`SeparatorIter`](crate::SeparatorIter), the separators are found with a predicate on
+/// the Rabin rolling hash, but chunks are at least `min_size` bytes and at most `max_size`
+/// bytes long. While looking for a separator, the last position satisfying an easier backup
+/// predicate is remembered: when a chunk reaches `max_size` bytes without a separator, it is
+/// cut at this backup breakpoint, which is content-defined too, and only at `max_size` if
+/// there is none. The bytes after a backup breakpoint are kept to be separated again.
+///
+/// The hash of a separator is the rolling hash at its index: with [`ChunkStats`](crate::ChunkStats),
+/// use the main predicate to count the backup breakpoints as forced cuts, or a predicate
+/// accepting both to only count the cuts at `max_size`.
+#[derive(Debug)]
+pub struct TttdSeparatorIter<I, F, B> {
+    /// The iterator to separate.
+    iter: I,
+
+    /// The predicate used to determine if a separator is a separator boundary.
+    predicate: F,
+
+    /// The predicate used to determine if a separator is a backup breakpoint.
+    backup_predicate: B,
+
+    /// The rolling hash, reset at the start of each chunk.
+    rabin: Rabin64,
+
+    /// The minimum size of a chunk.
+    min_size: u64,
+
+    /// The maximum size of a chunk.
+    max_size: u64,
+
+    /// The number of bytes separated.
+    index: u64,
+
+    /// The index of the start of the current chunk.
+    chunk_start: u64,
+
+    /// The last backup breakpoint of the current chunk.
+    backup: Option<Separator>,
+
+    /// The bytes separated after the last backup breakpoint.
+    since_backup: Vec<u8>,
+
+    /// The bytes to separate again, before reading from the iterator.
+    replay: VecDeque<u8>,
+}
+
+impl<I> TttdSeparatorIter<I, fn(u64) -> bool, fn(u64) -> bool>
+where
+    I: Iterator<Item = u8>,
+{
+    /// Creates a new `TttdSeparatorIter`, with the same predicate and window as
+    /// [`SeparatorIter::new`], in chunks of 2 KiB to 32 KiB and with backup breakpoints twice as
+    /// frequent.
+    ///
+    /// [`SeparatorIter::new`]: crate::SeparatorIter::new
+    ///
+    /// # Arguments
+    ///
+    /// * `iter` - The iterator to separate.
+    pub fn new(iter: I) -> Self {
+        #[inline]
+        fn default_predicate(x: u64) -> bool {
+            const BITMASK: u64 = (1u64 << 13) - 1;
+            x & BITMASK == BITMASK
+        }
+
+        #[inline]
+        fn default_backup_predicate(x: u64) -> bool {
+            const BITMASK: u64 = (1u64 << 12) - 1;
+            x & BITMASK == BITMASK
+        }
+
+        Self::custom_new(
+            iter,
+            6,
+            2 * 1024,
+            32 * 1024,
+            default_predicate,
+            default_backup_predicate,
+        )
+    }
+}
+
+impl<I, F, B> TttdSeparatorIter<I, F, B>
+where
+    I: Iterator<Item = u8>,
+    F: BoundaryPredicate,
+    B: BoundaryPredicate,
+{
+    /// Creates a new `TttdSeparatorIter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `iter` - The iterator to separate.
+    /// * `separator_size_nb_bits` - The number of bits of the separator size.
+    /// * `min_size` - The minimum size of a chunk, except for the last chunk of the stream.
+    /// * `max_size` - The maximum size of a chunk.
+    /// * `predicate` - The predicate used to determine if a separator is a separator boundary.
+    /// * `backup_predicate` - The predicate used to determine if a separator is a backup
+    ///   breakpoint, satisfied more often than `predicate`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_size` is `0` or smaller than `min_size`.
+    pub fn custom_new(
+        iter: I,
+        separator_size_nb_bits: u32,
+        min_size: u64,
+        max_size: u64,
+        predicate: F,
+        backup_predicate: B,
+    ) -> Self {
+        assert!(
+            max_size > 0 && min_size <= max_size,
+            "the maximum size must not be 0 or smaller than the minimum size"
+        );
+        Self {
+            iter,
+            predicate,
+            backup_predicate,
+            rabin: Rabin64::new(separator_size_nb_bits),
+            min_size,
+            max_size,
+            index: 0,
+            chunk_start: 0,
+            backup: None,
+            since_backup: Vec::new(),
+            replay: VecDeque::new(),
+        }
+    }
+
+    /// Ends the current chunk with a separator.
+    fn cut(&mut self, separator: Separator) -> Separator {
+        for byte in self.since_backup.drain(..).rev() {
+            self.replay.push_front(byte);
+        }
+        self.index = separator.index;
+        self.chunk_start = separator.index;
+        self.backup = None;
+        self.rabin.reset();
+        separator
+    }
+}
+
+impl<I, F, B> Iterator for TttdSeparatorIter<I, F, B>
+where
+    I: Iterator<Item = u8>,
+    F: BoundaryPredicate,
+    B: BoundaryPredicate,
+{
+    type Item = Separator;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(byte) = self.replay.pop_front().or_else(|| self.iter.next()) {
+            self.rabin.slide(byte);
+            self.index += 1;
+            if self.backup.is_some() {
+                self.since_backup.push(byte);
+            }
+
+            let separator = Separator {
+                index: self.index,
+                hash: self.rabin.hash,
+            };
+            let size = self.index - self.chunk_start;
+            if size >= self.min_size {
+                if self.predicate.is_boundary(separator.hash, size) {
+                    self.since_backup.clear();
+                    return Some(self.cut(separator));
+                }
+                if self.backup_predicate.is_boundary(separator.hash, size) {
+                    self.backup = Some(separator);
+                    self.since_backup.clear();
+                }
+            }
+            if size == self.max_size {
+                let separator = self.backup.unwrap_or(separator);
+                return Some(self.cut(separator));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::pseudo_random;
+    use crate::*;
+
+    /// Returns the size of the first chunk of `data`, and `true` if it was forced.
+    fn reference_chunk_size(
+        data: &[u8],
+        min_size: usize,
+        max_size: usize,
+        predicate: impl Fn(u64) -> bool,
+        backup_predicate: impl Fn(u64) -> bool,
+    ) -> (usize, bool) {
+        let mut rabin = Rabin64::new(6);
+        let mut backup = None;
+        for (position, byte) in data.iter().enumerate() {
+            rabin.slide(*byte);
+            let size = position + 1;
+            if size >= min_size {
+                if predicate(rabin.hash) {
+                    return (size, false);
+                }
+                if backup_predicate(rabin.hash) {
+                    backup = Some(size);
+                }
+            }
+            if size == max_size {
+                return backup.map_or((size, true), |backup| (backup, false));
+            }
+        }
+        (data.len(), false)
+    }
+
+    #[test]
+    fn tttd_separators() {
+        let data = pseudo_random(200_000);
+        let predicate = |x: u64| x & 0x7ff == 0x7ff;
+        let backup_predicate = |x: u64| x & 0xff == 0xff;
+        let (min_size, max_size) = (256, 1_024);
+
+        let separators = TttdSeparatorIter::custom_new(
+            data.iter().copied(),
+            6,
+            min_size as u64,
+            max_size as u64,
+            predicate,
+            backup_predicate,
+        );
+        let chunks: Vec<Chunk> = ChunkIter::new(separators, data.len() as u64).collect();
+        assert!(chunks.len() > 100);
+
+        let mut offset = 0;
+        let mut nb_backups = 0;
+        for chunk in &chunks {
+            let (size, forced) = reference_chunk_size(
+                &data[offset..],
+                min_size,
+                max_size,
+                predicate,
+                backup_predicate,
+            );
+            assert_eq!(chunk.size, size as u64);
+            offset += size;
+            assert!((min_size..=max_size).contains(&size) || offset == data.len());
+            if !forced && !predicate(chunk.separator_hash) && offset < data.len() {
+                assert!(backup_predicate(chunk.separator_hash));
+                nb_backups += 1;
+            }
+        }
+        assert_eq!(offset, data.len());
+        assert!(nb_backups > 10);
+    }
+}